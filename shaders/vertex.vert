@@ -1,15 +1,19 @@
 #version 330 core
 
 layout (location = 0) in vec3 aPos;
-layout (location = 1) in vec2 aTexCoords;
+// the primitives' layout, the normal and tangent at 1 and 3 aren't used yet
+layout (location = 2) in vec2 aTexCoords;
+// per instance, takes locations 4 to 7
+layout (location = 4) in mat4 aModel;
 
 out vec2 TexCoord;
 
-uniform mat4 model;
-uniform mat4 view;
-uniform mat4 projection;
+layout (std140) uniform Camera {
+    mat4 view;
+    mat4 projection;
+};
 
 void main() {
-    gl_Position = projection * view * model * vec4(aPos, 1.0);
+    gl_Position = projection * view * aModel * vec4(aPos, 1.0);
     TexCoord = aTexCoords;
 }
@@ -0,0 +1,442 @@
+//! Packs lots of small images into a few big textures.
+//!
+//! ```ignore
+//! let mut builder = AtlasBuilder::new().padding(2).bleed(1);
+//! for path in ["icons/save.png", "icons/load.png"] {
+//!     builder.add_file(path)?;
+//! }
+//! let atlas = builder.build(&LoadOptions::new())?;
+//! let save = atlas.get("save").unwrap();
+//! atlas.page(save).bind(GL_TEXTURE0);
+//! ```
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use image::{DynamicImage, ImageReader, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+use super::Error;
+use super::texture::{LoadOptions, Texture};
+
+/// Where an image ended up, in texture coordinates. `min` is the bottom left
+/// corner, since pages are uploaded flipped like every other [`Texture`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct UvRect {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+}
+
+/// One packed image.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AtlasEntry {
+    /// Index into the atlas' pages.
+    pub page: usize,
+    /// Pixel position on the page, from the top left, without the bleed.
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub uv: UvRect,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AtlasPage {
+    pub width: u32,
+    pub height: u32,
+    /// The page image, relative to the layout file, when it was saved.
+    pub file: Option<String>,
+}
+
+/// Where every image went, without the pixels. Serializes to JSON so a
+/// packed atlas can be loaded again without packing it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AtlasLayout {
+    pub pages: Vec<AtlasPage>,
+    pub entries: BTreeMap<String, AtlasEntry>,
+}
+
+impl AtlasLayout {
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Checks that every entry is on a page that exists and inside it, and
+    /// that page files stay next to the layout: no absolute paths, no `..`.
+    pub fn validate(&self) -> Result<(), Error> {
+        for (i, page) in self.pages.iter().enumerate() {
+            let Some(file) = &page.file else { continue };
+            let escapes = Path::new(file)
+                .components()
+                .any(|c| matches!(c, Component::ParentDir | Component::RootDir | Component::Prefix(_)));
+            if escapes {
+                return Err(Error::TextureFile(format!("page {} file {:?} isn't relative to the layout", i, file)));
+            }
+        }
+        for (name, entry) in &self.entries {
+            let page = self.pages.get(entry.page).ok_or_else(|| {
+                Error::TextureFile(format!("{} is on page {}, there are {}", name, entry.page, self.pages.len()))
+            })?;
+            let inside = entry.x.checked_add(entry.width).is_some_and(|right| right <= page.width)
+                && entry.y.checked_add(entry.height).is_some_and(|bottom| bottom <= page.height);
+            if !inside {
+                return Err(Error::TextureFile(format!(
+                    "{} at {},{} {}x{} doesn't fit its {}x{} page",
+                    name, entry.x, entry.y, entry.width, entry.height, page.width, page.height
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Collects images and packs them into pages.
+///
+/// Each image gets `bleed` pixels of its own edge copied around it, so
+/// linear filtering and mipmaps don't pull in the neighbours, then `padding`
+/// empty pixels before the next one.
+#[derive(Debug, Clone)]
+pub struct AtlasBuilder {
+    images: Vec<(String, DynamicImage)>,
+    page_width: u32,
+    page_height: u32,
+    padding: u32,
+    bleed: u32,
+}
+
+impl Default for AtlasBuilder {
+    fn default() -> Self {
+        Self { images: Vec::new(), page_width: 2048, page_height: 2048, padding: 2, bleed: 1 }
+    }
+}
+
+impl AtlasBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The size of every page, 2048x2048 by default.
+    pub fn page_size(self, width: u32, height: u32) -> Self {
+        Self { page_width: width, page_height: height, ..self }
+    }
+
+    /// Empty pixels between images, 2 by default.
+    pub fn padding(self, padding: u32) -> Self {
+        Self { padding, ..self }
+    }
+
+    /// How far each image's edge pixels get copied outwards, 1 by default.
+    pub fn bleed(self, bleed: u32) -> Self {
+        Self { bleed, ..self }
+    }
+
+    /// Adds an image file, named after the file without its extension.
+    pub fn add_file(&mut self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        let name = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+        let img = ImageReader::open(path)?.decode()?;
+        self.add_image(name, img);
+        Ok(())
+    }
+
+    pub fn add_image(&mut self, name: impl Into<String>, img: DynamicImage) {
+        self.images.push((name.into(), img));
+    }
+
+    /// Packs the images on the CPU, returning the layout and the RGBA8 page
+    /// images top row first.
+    pub fn pack(&self) -> Result<(AtlasLayout, Vec<RgbaImage>), Error> {
+        let margin = 2 * self.bleed + self.padding;
+        // biggest first packs tighter
+        let mut order: Vec<usize> = (0..self.images.len()).collect();
+        order.sort_by_key(|&i| {
+            let img = &self.images[i].1;
+            std::cmp::Reverse((img.height(), img.width()))
+        });
+
+        let mut layout = AtlasLayout::default();
+        let mut skylines: Vec<Skyline> = Vec::new();
+        let mut pages: Vec<RgbaImage> = Vec::new();
+        for i in order {
+            let (name, img) = &self.images[i];
+            if layout.entries.contains_key(name) {
+                return Err(Error::DuplicateAtlasName(name.clone()));
+            }
+            if img.width() == 0 || img.height() == 0 {
+                return Err(Error::TextureSize(format!("{} is {}x{}, atlas images can't be empty", name, img.width(), img.height())));
+            }
+            let (width, height) = (img.width() + margin, img.height() + margin);
+            if width > self.page_width || height > self.page_height {
+                return Err(Error::TextureSize(format!(
+                    "{} is {}x{} with bleed and padding, bigger than a {}x{} page",
+                    name, width, height, self.page_width, self.page_height
+                )));
+            }
+            let placed = skylines.iter_mut().enumerate().find_map(|(page, s)| s.insert(width, height).map(|at| (page, at)));
+            let (page, (x, y)) = match placed {
+                Some(placed) => placed,
+                None => {
+                    let mut skyline = Skyline::new(self.page_width, self.page_height);
+                    let at = skyline.insert(width, height).unwrap();
+                    skylines.push(skyline);
+                    pages.push(RgbaImage::new(self.page_width, self.page_height));
+                    (pages.len() - 1, at)
+                }
+            };
+
+            let (x, y) = (x + self.bleed, y + self.bleed);
+            blit_with_bleed(&mut pages[page], &img.to_rgba8(), x, y, self.bleed);
+            let (page_width, page_height) = (self.page_width as f32, self.page_height as f32);
+            let uv = UvRect {
+                min: [x as f32 / page_width, 1.0 - (y + img.height()) as f32 / page_height],
+                max: [(x + img.width()) as f32 / page_width, 1.0 - y as f32 / page_height],
+            };
+            layout.entries.insert(name.clone(), AtlasEntry { page, x, y, width: img.width(), height: img.height(), uv });
+        }
+        layout.pages = pages
+            .iter()
+            .map(|p| AtlasPage { width: p.width(), height: p.height(), file: None })
+            .collect();
+        Ok((layout, pages))
+    }
+
+    /// Packs the images and uploads every page as a [`Texture`].
+    pub fn build(&self, options: &LoadOptions) -> Result<Atlas, Error> {
+        let (layout, pages) = self.pack()?;
+        let pages = pages
+            .into_iter()
+            .map(|page| upload_page(DynamicImage::ImageRgba8(page), options))
+            .collect::<Result<_, _>>()?;
+        Ok(Atlas { layout, pages })
+    }
+
+    /// Packs the images and writes the layout to `json_path`, with the pages
+    /// as PNGs next to it named `<stem>_<page>.png`. Load it back with
+    /// [`Atlas::open`].
+    pub fn save(&self, json_path: impl AsRef<Path>) -> Result<AtlasLayout, Error> {
+        let json_path = json_path.as_ref();
+        let stem = json_path.file_stem().unwrap_or_default().to_string_lossy();
+        let (mut layout, pages) = self.pack()?;
+        for (i, (page, image)) in layout.pages.iter_mut().zip(&pages).enumerate() {
+            let file = format!("{}_{}.png", stem, i);
+            image.save(json_path.with_file_name(&file))?;
+            page.file = Some(file);
+        }
+        fs::write(json_path, layout.to_json()?)?;
+        Ok(layout)
+    }
+}
+
+/// Packed images with their pages on the GPU.
+pub struct Atlas {
+    pub layout: AtlasLayout,
+    pub pages: Vec<Texture>,
+}
+
+impl Atlas {
+    /// Loads an atlas written by [`AtlasBuilder::save`].
+    pub fn open(json_path: impl AsRef<Path>, options: &LoadOptions) -> Result<Self, Error> {
+        let json_path = json_path.as_ref();
+        let layout = AtlasLayout::from_json(&fs::read_to_string(json_path)?)?;
+        layout.validate()?;
+        let mut pages = Vec::with_capacity(layout.pages.len());
+        for page in &layout.pages {
+            let file = page
+                .file
+                .as_ref()
+                .ok_or_else(|| Error::TextureFile(format!("{} has a page without a file", json_path.display())))?;
+            let path: PathBuf = json_path.with_file_name(file);
+            let img = ImageReader::open(&path)?.decode()?;
+            if (img.width(), img.height()) != (page.width, page.height) {
+                return Err(Error::TextureFile(format!(
+                    "{} is {}x{}, the layout says {}x{}",
+                    path.display(), img.width(), img.height(), page.width, page.height
+                )));
+            }
+            pages.push(upload_page(img, options)?);
+        }
+        Ok(Self { layout, pages })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&AtlasEntry> {
+        self.layout.entries.get(name)
+    }
+
+    /// The texture an entry is on.
+    pub fn page(&self, entry: &AtlasEntry) -> &Texture {
+        &self.pages[entry.page]
+    }
+}
+
+fn upload_page(img: DynamicImage, options: &LoadOptions) -> Result<Texture, Error> {
+    let tex = Texture::new()?;
+    tex.load_image(&img.flipv(), options)?;
+    Ok(tex)
+}
+
+/// Copies `src` onto `page` at `x`, `y` and smears its edge pixels `bleed`
+/// pixels outwards, corners included.
+fn blit_with_bleed(page: &mut RgbaImage, src: &RgbaImage, x: u32, y: u32, bleed: u32) {
+    let (width, height) = src.dimensions();
+    for py in y - bleed..y + height + bleed {
+        for px in x - bleed..x + width + bleed {
+            let sx = px.saturating_sub(x).min(width - 1);
+            let sy = py.saturating_sub(y).min(height - 1);
+            page.put_pixel(px, py, *src.get_pixel(sx, sy));
+        }
+    }
+}
+
+/// Bottom left skyline packing. The skyline is the top edge of everything
+/// placed so far, stored as segments left to right. Here "bottom" is the top
+/// of the page, y grows downwards like the page images.
+struct Skyline {
+    width: u32,
+    height: u32,
+    /// `(x, y, width)` of each segment.
+    segments: Vec<(u32, u32, u32)>,
+}
+
+impl Skyline {
+    fn new(width: u32, height: u32) -> Self {
+        Self { width, height, segments: vec![(0, 0, width)] }
+    }
+
+    /// Where a `width` by `height` rect would sit if it started at segment
+    /// `i`, resting on the highest segment under it.
+    fn fit(&self, i: usize, width: u32, height: u32) -> Option<u32> {
+        let x = self.segments[i].0;
+        if x + width > self.width {
+            return None;
+        }
+        let mut y = 0;
+        let mut covered = 0;
+        for &(_, seg_y, seg_width) in &self.segments[i..] {
+            y = y.max(seg_y);
+            covered += seg_width;
+            if covered >= width {
+                break;
+            }
+        }
+        (y + height <= self.height).then_some(y)
+    }
+
+    fn insert(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let (i, y) = (0..self.segments.len())
+            .filter_map(|i| self.fit(i, width, height).map(|y| (i, y)))
+            .min_by_key(|&(i, y)| (y + height, self.segments[i].0))?;
+        let x = self.segments[i].0;
+
+        // the new segment replaces everything it covers
+        self.segments.insert(i, (x, y + height, width));
+        let end = x + width;
+        let j = i + 1;
+        while j < self.segments.len() {
+            let (seg_x, seg_y, seg_width) = self.segments[j];
+            if seg_x >= end {
+                break;
+            }
+            if seg_x + seg_width <= end {
+                self.segments.remove(j);
+            } else {
+                self.segments[j] = (end, seg_y, seg_x + seg_width - end);
+                break;
+            }
+        }
+        // neighbours at the same height are one segment
+        self.segments.dedup_by(|next, prev| {
+            if prev.1 == next.1 {
+                prev.2 += next.2;
+                true
+            } else {
+                false
+            }
+        });
+        Some((x, y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: u32, height: u32, value: u8) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_pixel(width, height, image::Rgba([value, value, value, 255])))
+    }
+
+    #[test]
+    fn packed_images_and_bleed_dont_overlap() {
+        let mut builder = AtlasBuilder::new().page_size(64, 64).padding(1).bleed(2);
+        for i in 0..12 {
+            builder.add_image(format!("img{}", i), solid(5 + i % 4 * 3, 4 + i % 3 * 5, 10 + i as u8));
+        }
+        let (layout, pages) = builder.pack().unwrap();
+        let entries: Vec<_> = layout.entries.values().collect();
+        for (i, a) in entries.iter().enumerate() {
+            // every pixel of the image made it, and its bleed is the edge color
+            let value = pages[a.page].get_pixel(a.x, a.y).0[0];
+            assert_eq!(pages[a.page].get_pixel(a.x - 2, a.y - 2).0[0], value);
+            assert_eq!(pages[a.page].get_pixel(a.x + a.width + 1, a.y + a.height + 1).0[0], value);
+            for b in &entries[i + 1..] {
+                // two bleeds and the padding between them
+                let gap = 5;
+                let apart = a.page != b.page
+                    || a.x + a.width + gap <= b.x
+                    || b.x + b.width + gap <= a.x
+                    || a.y + a.height + gap <= b.y
+                    || b.y + b.height + gap <= a.y;
+                assert!(apart, "{:?} overlaps {:?}", a, b);
+            }
+        }
+        assert_eq!(AtlasLayout::from_json(&layout.to_json().unwrap()).unwrap(), layout);
+    }
+
+    #[test]
+    fn empty_image_is_an_error() {
+        let mut builder = AtlasBuilder::new();
+        builder.add_image("empty", solid(0, 8, 0));
+        assert!(matches!(builder.pack(), Err(Error::TextureSize(_))));
+    }
+
+    #[test]
+    fn duplicate_name_is_an_error() {
+        let mut builder = AtlasBuilder::new();
+        builder.add_image("a", solid(2, 2, 0));
+        builder.add_image("a", solid(3, 3, 0));
+        assert!(matches!(builder.pack(), Err(Error::DuplicateAtlasName(name)) if name == "a"));
+    }
+
+    #[test]
+    fn layouts_are_validated() {
+        let mut builder = AtlasBuilder::new().page_size(32, 32);
+        builder.add_image("a", solid(4, 4, 0));
+        let (layout, _) = builder.pack().unwrap();
+        layout.validate().unwrap();
+
+        let mut bad = layout.clone();
+        bad.entries.get_mut("a").unwrap().page = 1;
+        assert!(matches!(bad.validate(), Err(Error::TextureFile(_))));
+
+        let mut bad = layout.clone();
+        bad.entries.get_mut("a").unwrap().x = 30;
+        assert!(matches!(bad.validate(), Err(Error::TextureFile(_))));
+
+        let mut bad = layout.clone();
+        bad.entries.get_mut("a").unwrap().height = u32::MAX;
+        assert!(matches!(bad.validate(), Err(Error::TextureFile(_))));
+
+        for file in ["../secret.png", "/etc/page.png", "pages/../../page.png"] {
+            let mut bad = layout.clone();
+            bad.pages[0].file = Some(file.to_string());
+            assert!(matches!(bad.validate(), Err(Error::TextureFile(_))), "{}", file);
+        }
+        let mut good = layout;
+        good.pages[0].file = Some("pages/atlas_0.png".to_string());
+        good.validate().unwrap();
+    }
+}
@@ -0,0 +1,476 @@
+//! Checks the shaders without a GL context, so it can run on CI machines
+//! that don't have a GPU.
+//!
+//! ```text
+//! cargo run --no-default-features --bin validate_shaders -- [shader dir] [--host file.rs]...
+//! ```
+//!
+//! Without the default `window` feature nothing depends on SDL2, so it builds
+//! on machines that don't have it installed.
+//!
+//! Every `.vert`, `.geom` and `.frag` file in the directory (default
+//! `shaders/`) is preprocessed and parsed. Then vertex outputs are matched
+//! against fragment inputs, and uniforms are compared against the names the
+//! host code (default `src/main.rs`) sets. Exits with 1 if anything is wrong.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use learn_openGL as learn;
+use learn::diagnostic::{Diagnostic, Severity};
+use learn::glsl::{self, ShaderInterface, Storage};
+use learn::preprocess::{preprocess_file, Preprocessed};
+use learn::shader::ShaderType;
+
+/// Host functions that take a uniform name, as their first string argument.
+/// `bind` is [`TextureUnits::bind`](learn::texture_unit::TextureUnits::bind).
+const UNIFORM_SETTERS: &[&str] = &["set_uniform", "uniform_location", "setBool", "setInt", "setFloat", "setMat4", "bind"];
+/// Host functions whose first argument is a uniform block name.
+const BLOCK_BINDERS: &[&str] = &["bind_uniform_block"];
+
+struct Parsed {
+    path: PathBuf,
+    ty: ShaderType,
+    pre: Preprocessed,
+    iface: ShaderInterface,
+}
+
+#[derive(Default)]
+struct Report {
+    errors: usize,
+    warnings: usize,
+}
+
+impl Report {
+    fn push(&mut self, d: Diagnostic) {
+        match d.severity {
+            Severity::Error => self.errors += 1,
+            Severity::Warning => self.warnings += 1,
+            Severity::Info => (),
+        }
+        eprintln!("{}", d);
+    }
+
+    /// Reports something about a declaration in `shader`.
+    fn at(&mut self, shader: &Parsed, file: u32, line: u32, severity: Severity, message: String) {
+        let mut d = Diagnostic {
+            file: Some(file),
+            path: None,
+            line: Some(line),
+            column: None,
+            severity,
+            message,
+            excerpt: None,
+        };
+        locate(&mut d, shader);
+        self.push(d);
+    }
+
+    fn unlocated(&mut self, path: &Path, severity: Severity, message: String) {
+        self.push(Diagnostic {
+            file: None,
+            path: None,
+            line: None,
+            column: None,
+            severity,
+            message: format!("{}: {}", path.display(), message),
+            excerpt: None,
+        });
+    }
+}
+
+/// Points a diagnostic at the file it came from, through the `#line`s the
+/// preprocessor wrote.
+fn locate(d: &mut Diagnostic, shader: &Parsed) {
+    match d.file.and_then(|i| shader.pre.files.get(i as usize)) {
+        Some(file) => {
+            d.path = Some(file.path.clone());
+            d.excerpt = d.render_excerpt(&file.text);
+        }
+        None => d.path = Some(shader.path.clone()),
+    }
+}
+
+fn stage_of(path: &Path) -> Option<ShaderType> {
+    match path.extension()?.to_str()? {
+        "vert" => Some(ShaderType::Vertex),
+        "geom" => Some(ShaderType::Geometry),
+        "frag" => Some(ShaderType::Fragment),
+        _ => None,
+    }
+}
+
+fn main() -> ExitCode {
+    let mut dir = PathBuf::from("shaders");
+    let mut hosts = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--host" => match args.next() {
+                Some(host) => hosts.push(PathBuf::from(host)),
+                None => {
+                    eprintln!("--host needs a file");
+                    return ExitCode::FAILURE;
+                }
+            },
+            _ => dir = PathBuf::from(arg),
+        }
+    }
+    if hosts.is_empty() {
+        hosts.push(PathBuf::from("src/main.rs"));
+    }
+
+    let mut report = Report::default();
+    let shaders = match load_shaders(&dir, &mut report) {
+        Ok(shaders) => shaders,
+        Err(e) => {
+            eprintln!("Can't read {}: {}", dir.display(), e);
+            return ExitCode::FAILURE;
+        }
+    };
+    for shader in &shaders {
+        if !shader.iface.functions.iter().any(|f| f == "main") {
+            report.unlocated(&shader.path, Severity::Error, "no `main` function".to_string());
+        }
+    }
+    check_interfaces(&shaders, &mut report);
+    let (set, bound) = host_names(&hosts, &mut report);
+    check_uniforms(&shaders, &set, &bound, &hosts[0], &mut report);
+
+    println!(
+        "Checked {} shader(s): {} error(s), {} warning(s)",
+        shaders.len(),
+        report.errors,
+        report.warnings
+    );
+    if report.errors > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Preprocesses and parses every stage file, reporting the ones that fail.
+fn load_shaders(dir: &Path, report: &mut Report) -> std::io::Result<Vec<Parsed>> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<_, _>>()?;
+    paths.sort();
+
+    let mut shaders = Vec::new();
+    for path in paths {
+        let ty = match stage_of(&path) {
+            Some(ty) => ty,
+            // includes get checked through the stages that use them
+            None => continue,
+        };
+        let pre = match preprocess_file(&path, &[]) {
+            Ok(pre) => pre,
+            Err(e) => {
+                report.unlocated(&path, Severity::Error, e.to_string());
+                continue;
+            }
+        };
+        match glsl::parse(&pre.source, ty) {
+            Ok(iface) => shaders.push(Parsed { path, ty, pre, iface }),
+            Err(mut d) => {
+                let shader = Parsed { path, ty, pre, iface: ShaderInterface::default() };
+                locate(&mut d, &shader);
+                report.push(d);
+            }
+        }
+    }
+    Ok(shaders)
+}
+
+/// Shaders with the same file stem are one program. If the directory only
+/// has one vertex and one fragment shader they're paired no matter the names.
+fn programs(shaders: &[Parsed]) -> Vec<Vec<&Parsed>> {
+    let vertex: Vec<&Parsed> = shaders.iter().filter(|s| s.ty == ShaderType::Vertex).collect();
+    let fragment: Vec<&Parsed> = shaders.iter().filter(|s| s.ty == ShaderType::Fragment).collect();
+    let geometry: Vec<&Parsed> = shaders.iter().filter(|s| s.ty == ShaderType::Geometry).collect();
+    if vertex.len() == 1 && fragment.len() == 1 && geometry.len() <= 1 {
+        return vec![vertex.into_iter().chain(geometry).chain(fragment).collect()];
+    }
+    let stem = |s: &Parsed| s.path.file_stem().map(|s| s.to_os_string());
+    vertex
+        .iter()
+        .map(|v| {
+            let mut program = vec![*v];
+            program.extend(geometry.iter().filter(|g| stem(g) == stem(v)));
+            program.extend(fragment.iter().filter(|f| stem(f) == stem(v)));
+            program
+        })
+        .filter(|p| p.len() > 1)
+        .collect()
+}
+
+/// Every input of a stage has to be written by the stage before it, with the
+/// same type.
+fn check_interfaces(shaders: &[Parsed], report: &mut Report) {
+    for program in programs(shaders) {
+        for pair in program.windows(2) {
+            let (prev, next) = (pair[0], pair[1]);
+            // geometry inputs are arrays of the vertex outputs
+            let arrayed = next.ty == ShaderType::Geometry;
+            for input in next.iface.with_storage(Storage::In) {
+                if input.name.starts_with("gl_") {
+                    continue;
+                }
+                match prev.iface.with_storage(Storage::Out).find(|o| o.name == input.name) {
+                    None => report.at(next, input.file, input.line, Severity::Error, format!(
+                        "input `{}` isn't written by {}",
+                        input.name,
+                        prev.path.display()
+                    )),
+                    Some(out) if out.ty != input.ty || (!arrayed && out.array != input.array) => {
+                        report.at(next, input.file, input.line, Severity::Error, format!(
+                            "input `{} {}` doesn't match `{} {}` in {}",
+                            input.ty,
+                            input.name,
+                            out.ty,
+                            out.name,
+                            prev.path.display()
+                        ))
+                    }
+                    Some(_) => (),
+                }
+            }
+            for block in next.iface.blocks.iter().filter(|b| b.storage == Storage::In) {
+                let matched = prev.iface.blocks.iter().find(|b| b.storage == Storage::Out && b.name == block.name);
+                match matched {
+                    None => report.at(next, block.file, block.line, Severity::Error, format!(
+                        "input block `{}` isn't written by {}",
+                        block.name,
+                        prev.path.display()
+                    )),
+                    Some(out) if out.members.iter().map(|m| (&m.ty, &m.name)).ne(block.members.iter().map(|m| (&m.ty, &m.name))) => {
+                        report.at(next, block.file, block.line, Severity::Error, format!(
+                            "input block `{}` has different members than in {}",
+                            block.name,
+                            prev.path.display()
+                        ))
+                    }
+                    Some(_) => (),
+                }
+            }
+            for output in prev.iface.with_storage(Storage::Out) {
+                let read = next.iface.with_storage(Storage::In).any(|i| i.name == output.name);
+                if !read {
+                    report.at(prev, output.file, output.line, Severity::Warning, format!(
+                        "output `{}` is never read by {}",
+                        output.name,
+                        next.path.display()
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// The uniform names the host files set and the block names they bind.
+fn host_names(hosts: &[PathBuf], report: &mut Report) -> (HashSet<String>, HashSet<String>) {
+    let mut set = HashSet::new();
+    let mut bound = HashSet::new();
+    for host in hosts {
+        match fs::read_to_string(host) {
+            Ok(text) => {
+                set.extend(string_args(&text, UNIFORM_SETTERS));
+                bound.extend(string_args(&text, BLOCK_BINDERS));
+            }
+            Err(e) => report.unlocated(host, Severity::Error, format!("can't read host file: {}", e)),
+        }
+    }
+    (set, bound)
+}
+
+/// Compares the declared uniforms against the names the host sets, `host`
+/// is where names no shader declares get reported.
+fn check_uniforms(shaders: &[Parsed], set: &HashSet<String>, bound: &HashSet<String>, host: &Path, report: &mut Report) {
+    for shader in shaders {
+        for u in shader.iface.with_storage(Storage::Uniform) {
+            let used = shader.iface.is_used(&u.name);
+            match (set.contains(&u.name), used) {
+                // the driver drops it, so setting it fails at runtime
+                (true, false) => report.at(shader, u.file, u.line, Severity::Error, format!(
+                    "uniform `{}` is set by the host but never used, the driver will remove it",
+                    u.name
+                )),
+                (false, true) => report.at(shader, u.file, u.line, Severity::Warning, format!(
+                    "uniform `{}` is never set by the host",
+                    u.name
+                )),
+                (false, false) => report.at(shader, u.file, u.line, Severity::Warning, format!(
+                    "uniform `{}` is never used",
+                    u.name
+                )),
+                (true, true) => (),
+            }
+        }
+        for block in shader.iface.blocks.iter().filter(|b| b.storage == Storage::Uniform) {
+            let used = shader.iface.block_used(block);
+            match (bound.contains(&block.name), used) {
+                (true, false) => report.at(shader, block.file, block.line, Severity::Error, format!(
+                    "uniform block `{}` is bound by the host but never used, the driver will remove it",
+                    block.name
+                )),
+                (false, _) => report.at(shader, block.file, block.line, Severity::Warning, format!(
+                    "uniform block `{}` is never bound by the host",
+                    block.name
+                )),
+                (true, true) => (),
+            }
+        }
+    }
+
+    let declared = |name: &String| {
+        shaders.iter().any(|s| s.iface.with_storage(Storage::Uniform).any(|u| &u.name == name))
+    };
+    let declared_block = |name: &String| {
+        shaders.iter().any(|s| s.iface.blocks.iter().any(|b| b.storage == Storage::Uniform && &b.name == name))
+    };
+    let mut undeclared: Vec<_> = set.iter().filter(|n| !declared(n)).collect();
+    undeclared.sort();
+    for name in undeclared {
+        report.unlocated(host, Severity::Error, format!("sets uniform `{}` but no shader declares it", name));
+    }
+    let mut undeclared: Vec<_> = bound.iter().filter(|n| !declared_block(n)).collect();
+    undeclared.sort();
+    for name in undeclared {
+        report.unlocated(host, Severity::Error, format!("binds uniform block `{}` but no shader declares it", name));
+    }
+}
+
+/// Finds the first string literal argument of every call to one of `fns`,
+/// eg `"model"` out of `program.set_uniform("model", &model)` or `"face"`
+/// out of `units.bind(&program, "face", &tex)`.
+fn string_args(text: &str, fns: &[&str]) -> Vec<String> {
+    let mut out = Vec::new();
+    for f in fns {
+        let pattern = format!("{}(", f);
+        for (i, _) in text.match_indices(&pattern) {
+            // skip `my_set_uniform(` and friends
+            let before = text[..i].chars().next_back();
+            if before.is_some_and(|c| c.is_alphanumeric() || c == '_') {
+                continue;
+            }
+            let rest = &text[i + pattern.len()..];
+            let Some(start) = rest.find('"') else { continue };
+            if rest.find(')').is_some_and(|close| close < start) {
+                continue;
+            }
+            let rest = &rest[start + 1..];
+            if let Some(end) = rest.find('"') {
+                out.push(rest[..end].to_string());
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use learn::preprocess::SourceFile;
+
+    fn shader(path: &str, source: &str) -> Parsed {
+        let path = PathBuf::from(path);
+        let ty = stage_of(&path).unwrap();
+        let iface = glsl::parse(source, ty).unwrap();
+        let pre = Preprocessed {
+            source: source.to_string(),
+            files: vec![SourceFile { path: path.clone(), text: source.to_string() }],
+        };
+        Parsed { path, ty, pre, iface }
+    }
+
+    fn interfaces(vert: &str, frag: &str) -> Report {
+        let mut report = Report::default();
+        check_interfaces(&[shader("a.vert", vert), shader("a.frag", frag)], &mut report);
+        report
+    }
+
+    #[test]
+    fn matching_interfaces_pass() {
+        let report = interfaces(
+            "out vec2 uv;\nout Data { vec3 normal; } data;\nvoid main() {}\n",
+            "in vec2 uv;\nin Data { vec3 normal; } data;\nvoid main() {}\n",
+        );
+        assert_eq!((report.errors, report.warnings), (0, 0));
+    }
+
+    #[test]
+    fn mismatched_interfaces_are_errors() {
+        // wrong name
+        let report = interfaces("out vec2 uv;\nvoid main() {}\n", "in vec2 texcoord;\nvoid main() {}\n");
+        assert_eq!((report.errors, report.warnings), (1, 1));
+        // wrong type
+        let report = interfaces("out vec2 uv;\nvoid main() {}\n", "in vec3 uv;\nvoid main() {}\n");
+        assert_eq!((report.errors, report.warnings), (1, 0));
+        // block members differ
+        let report = interfaces(
+            "out Data { vec3 normal; } data;\nvoid main() {}\n",
+            "in Data { vec4 normal; } data;\nvoid main() {}\n",
+        );
+        assert_eq!(report.errors, 1);
+    }
+
+    #[test]
+    fn errors_point_through_line_directives() {
+        let mut parsed = shader("a.frag", "#line 1 1\nin vec3 normal;\nvoid main() {}\n");
+        let include = PathBuf::from("lighting.glsl");
+        parsed.pre.files.push(SourceFile { path: include.clone(), text: "in vec3 normal;\n".to_string() });
+        let mut d = Diagnostic {
+            file: Some(parsed.iface.variables[0].file),
+            path: None,
+            line: Some(parsed.iface.variables[0].line),
+            column: None,
+            severity: Severity::Error,
+            message: String::new(),
+            excerpt: None,
+        };
+        locate(&mut d, &parsed);
+        assert_eq!(d.path, Some(include));
+        assert_eq!(d.line, Some(1));
+        assert!(d.excerpt.unwrap().contains("in vec3 normal;"));
+    }
+
+    #[test]
+    fn uniforms_against_host_names() {
+        let host = r#"
+            program.set_uniform("used_and_set", &1.0).unwrap();
+            program.set_uniform("unused_but_set", &1.0).unwrap();
+            units.bind(&program, "texture_unit", &tex).unwrap();
+            program.set_uniform("not_declared", &1.0).unwrap();
+            program.bind_uniform_block("Camera", 0).unwrap();
+            my_set_uniform("ignored", 1.0);
+        "#;
+        let set: HashSet<String> = string_args(host, UNIFORM_SETTERS).into_iter().collect();
+        let bound: HashSet<String> = string_args(host, BLOCK_BINDERS).into_iter().collect();
+        let expected: HashSet<String> = ["used_and_set", "unused_but_set", "texture_unit", "not_declared"]
+            .map(String::from)
+            .into();
+        assert_eq!(set, expected);
+        assert_eq!(bound, HashSet::from(["Camera".to_string()]));
+
+        let frag = shader(
+            "a.frag",
+            "uniform float used_and_set;\n\
+             uniform float unused_but_set;\n\
+             uniform sampler2D texture_unit;\n\
+             uniform float used_not_set;\n\
+             uniform float neither;\n\
+             uniform Camera { mat4 view; };\n\
+             out vec4 color;\n\
+             void main() { color = texture(texture_unit, vec2(used_and_set, used_not_set)) * view[0]; }\n",
+        );
+        let mut report = Report::default();
+        check_uniforms(&[frag], &set, &bound, Path::new("main.rs"), &mut report);
+        // set but unused, and set but not declared
+        assert_eq!(report.errors, 2);
+        // used but never set, and neither
+        assert_eq!(report.warnings, 2);
+    }
+}
@@ -0,0 +1,155 @@
+use std::marker::PhantomData;
+use std::ops::Range;
+
+use bytemuck::Pod;
+use gl33::{*, global_loader::*};
+
+use super::{BufferObject, BufferType, Error};
+
+/// How often the data gets written and who reads it, a hint for where the
+/// driver puts the buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(u32)]
+pub enum Usage {
+    /// Written once, drawn from many times. Meshes.
+    #[default]
+    StaticDraw = GL_STATIC_DRAW.0,
+    StaticRead = GL_STATIC_READ.0,
+    StaticCopy = GL_STATIC_COPY.0,
+    /// Written now and then, drawn from many times. Uniform buffers.
+    DynamicDraw = GL_DYNAMIC_DRAW.0,
+    DynamicRead = GL_DYNAMIC_READ.0,
+    DynamicCopy = GL_DYNAMIC_COPY.0,
+    /// Written every frame and drawn from a few times. Particles.
+    StreamDraw = GL_STREAM_DRAW.0,
+    StreamRead = GL_STREAM_READ.0,
+    StreamCopy = GL_STREAM_COPY.0,
+}
+
+/// A buffer object holding a slice of `T`.
+///
+/// Updates and read backs go through `GL_COPY_WRITE_BUFFER` and
+/// `GL_COPY_READ_BUFFER`, so they don't disturb the element buffer of the
+/// bound VAO or anything else bound to the buffer's own target.
+pub struct Buffer<T: Pod> {
+    buffer: BufferObject,
+    ty: BufferType,
+    len: usize,
+    usage: Usage,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Pod> Buffer<T> {
+    /// Makes an empty buffer, give it data with
+    /// [`set_data`](Self::set_data) or [`resize`](Self::resize).
+    pub fn new(ty: BufferType, usage: Usage) -> Result<Self, Error> {
+        Ok(Self { buffer: BufferObject::new()?, ty, len: 0, usage, _marker: PhantomData })
+    }
+
+    /// Makes a buffer holding a copy of `data`.
+    pub fn from_slice(ty: BufferType, data: &[T], usage: Usage) -> Result<Self, Error> {
+        let mut buffer = Self::new(ty, usage)?;
+        buffer.set_data(data);
+        Ok(buffer)
+    }
+
+    /// Replaces the whole buffer with `data`, which can be any length.
+    pub fn set_data(&mut self, data: &[T]) {
+        self.respecify(data.len(), data.as_ptr().cast());
+    }
+
+    /// Writes `data` over the elements starting at `offset`, with
+    /// `glBufferSubData`. Errors if that goes past the end, the buffer doesn't
+    /// grow.
+    pub fn update(&self, offset: usize, data: &[T]) -> Result<(), Error> {
+        self.check_range(offset..offset + data.len())?;
+        let bytes: &[u8] = bytemuck::cast_slice(data);
+        unsafe {
+            glBindBuffer(GL_COPY_WRITE_BUFFER, self.buffer.0);
+            glBufferSubData(GL_COPY_WRITE_BUFFER, (offset * size_of::<T>()) as isize, bytes.len() as isize, bytes.as_ptr().cast());
+            glBindBuffer(GL_COPY_WRITE_BUFFER, 0);
+        }
+        Ok(())
+    }
+
+    /// Reallocates the buffer for `len` elements. The old contents are
+    /// dropped, not copied, which lets the driver hand out fresh memory
+    /// instead of waiting for draws still reading the old data.
+    pub fn resize(&mut self, len: usize) {
+        self.respecify(len, std::ptr::null());
+    }
+
+    /// Throws away the contents but keeps the size, see
+    /// [`resize`](Self::resize). Do this before rewriting a streamed buffer
+    /// every frame.
+    pub fn orphan(&mut self) {
+        self.resize(self.len);
+    }
+
+    /// Copies the whole buffer back with `glGetBufferSubData`.
+    pub fn read(&self) -> Vec<T> {
+        self.read_range(0..self.len).expect("the whole buffer is in range")
+    }
+
+    /// Copies `range` of the buffer back, errors if it goes past the end.
+    pub fn read_range(&self, range: Range<usize>) -> Result<Vec<T>, Error> {
+        self.check_range(range.clone())?;
+        let mut out = vec![T::zeroed(); range.len()];
+        let bytes: &mut [u8] = bytemuck::cast_slice_mut(&mut out);
+        unsafe {
+            glBindBuffer(GL_COPY_READ_BUFFER, self.buffer.0);
+            glGetBufferSubData(GL_COPY_READ_BUFFER, (range.start * size_of::<T>()) as isize, bytes.len() as isize, bytes.as_mut_ptr().cast());
+            glBindBuffer(GL_COPY_READ_BUFFER, 0);
+        }
+        Ok(out)
+    }
+
+    /// Binds the buffer to its target.
+    pub fn bind(&self) {
+        self.buffer.bind(self.ty);
+    }
+
+    /// Attaches the buffer to an indexed binding point of its target, eg a
+    /// uniform buffer binding.
+    pub fn bind_base(&self, binding: u32) {
+        unsafe { glBindBufferBase(GLenum(self.ty as u32), binding, self.buffer.0) };
+    }
+
+    /// The number of `T`s in the buffer.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn ty(&self) -> BufferType {
+        self.ty
+    }
+
+    pub fn usage(&self) -> Usage {
+        self.usage
+    }
+
+    /// The underlying buffer object.
+    pub fn buffer(&self) -> &BufferObject {
+        &self.buffer
+    }
+
+    fn respecify(&mut self, len: usize, data: *const std::ffi::c_void) {
+        unsafe {
+            glBindBuffer(GL_COPY_WRITE_BUFFER, self.buffer.0);
+            glBufferData(GL_COPY_WRITE_BUFFER, (len * size_of::<T>()) as isize, data, GLenum(self.usage as u32));
+            glBindBuffer(GL_COPY_WRITE_BUFFER, 0);
+        }
+        self.len = len;
+    }
+
+    fn check_range(&self, range: Range<usize>) -> Result<(), Error> {
+        if range.start > range.end || range.end > self.len {
+            return Err(Error::BufferRange { start: range.start, end: range.end, len: self.len });
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,602 @@
+//! Block compressed textures out of KTX1 and DDS files.
+//!
+//! BC1 to BC5 (S3TC and RGTC) are uploaded as is when the driver supports
+//! them. RGTC is core GL 3.0, S3TC needs `GL_EXT_texture_compression_s3tc`,
+//! and without it the blocks are decoded on the CPU instead.
+
+use std::fs;
+use std::path::Path;
+
+use gl33::*;
+use image::{DynamicImage, ImageBuffer, Pixel};
+
+use super::Error;
+use super::has_extension;
+
+// S3TC is an extension, so gl33 doesn't have these.
+const GL_COMPRESSED_RGB_S3TC_DXT1_EXT: GLenum = GLenum(0x83F0);
+const GL_COMPRESSED_RGBA_S3TC_DXT1_EXT: GLenum = GLenum(0x83F1);
+const GL_COMPRESSED_RGBA_S3TC_DXT3_EXT: GLenum = GLenum(0x83F2);
+const GL_COMPRESSED_RGBA_S3TC_DXT5_EXT: GLenum = GLenum(0x83F3);
+const GL_COMPRESSED_SRGB_S3TC_DXT1_EXT: GLenum = GLenum(0x8C4C);
+const GL_COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT: GLenum = GLenum(0x8C4D);
+const GL_COMPRESSED_SRGB_ALPHA_S3TC_DXT3_EXT: GLenum = GLenum(0x8C4E);
+const GL_COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT: GLenum = GLenum(0x8C4F);
+
+/// The block compression formats that can be loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockFormat {
+    /// DXT1 without alpha.
+    Bc1,
+    /// DXT1 with 1 bit alpha.
+    Bc1Alpha,
+    /// DXT3, 4 bit explicit alpha.
+    Bc2,
+    /// DXT5, interpolated alpha.
+    Bc3,
+    /// RGTC1, one channel.
+    Bc4,
+    /// RGTC2, two channels, usually a normal map's x and y.
+    Bc5,
+}
+
+impl BlockFormat {
+    /// Bytes in one 4x4 block.
+    pub fn block_bytes(self) -> usize {
+        match self {
+            BlockFormat::Bc1 | BlockFormat::Bc1Alpha | BlockFormat::Bc4 => 8,
+            BlockFormat::Bc2 | BlockFormat::Bc3 | BlockFormat::Bc5 => 16,
+        }
+    }
+
+    /// Bytes in a `width` by `height` image, partial blocks count as whole.
+    pub fn level_bytes(self, width: u32, height: u32) -> usize {
+        width.div_ceil(4) as usize * height.div_ceil(4) as usize * self.block_bytes()
+    }
+
+    /// The GL internal format. Only the S3TC formats have sRGB versions.
+    pub fn gl_format(self, srgb: bool) -> GLenum {
+        match (self, srgb) {
+            (BlockFormat::Bc1, false) => GL_COMPRESSED_RGB_S3TC_DXT1_EXT,
+            (BlockFormat::Bc1, true) => GL_COMPRESSED_SRGB_S3TC_DXT1_EXT,
+            (BlockFormat::Bc1Alpha, false) => GL_COMPRESSED_RGBA_S3TC_DXT1_EXT,
+            (BlockFormat::Bc1Alpha, true) => GL_COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT,
+            (BlockFormat::Bc2, false) => GL_COMPRESSED_RGBA_S3TC_DXT3_EXT,
+            (BlockFormat::Bc2, true) => GL_COMPRESSED_SRGB_ALPHA_S3TC_DXT3_EXT,
+            (BlockFormat::Bc3, false) => GL_COMPRESSED_RGBA_S3TC_DXT5_EXT,
+            (BlockFormat::Bc3, true) => GL_COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT,
+            (BlockFormat::Bc4, _) => GL_COMPRESSED_RED_RGTC1,
+            (BlockFormat::Bc5, _) => GL_COMPRESSED_RG_RGTC2,
+        }
+    }
+
+    /// Checks if the driver can take these blocks directly. Call it after
+    /// [`load_gl`](crate::load_gl).
+    pub fn is_supported(self, srgb: bool) -> bool {
+        match self {
+            BlockFormat::Bc4 | BlockFormat::Bc5 => true,
+            _ if srgb => {
+                has_extension("GL_EXT_texture_compression_s3tc")
+                    && (has_extension("GL_EXT_texture_sRGB") || has_extension("GL_EXT_texture_compression_s3tc_srgb"))
+            }
+            _ => has_extension("GL_EXT_texture_compression_s3tc"),
+        }
+    }
+
+    fn from_gl(format: u32) -> Option<(Self, bool)> {
+        let format = GLenum(format);
+        [BlockFormat::Bc1, BlockFormat::Bc1Alpha, BlockFormat::Bc2, BlockFormat::Bc3, BlockFormat::Bc4, BlockFormat::Bc5]
+            .into_iter()
+            .flat_map(|f| [(f, false), (f, true)])
+            .find(|&(f, srgb)| f.gl_format(srgb) == format)
+    }
+}
+
+/// A block compressed 2D image with its mip levels, top row first.
+#[derive(Debug, Clone)]
+pub struct CompressedImage {
+    pub format: BlockFormat,
+    /// The file says the colors are sRGB.
+    pub srgb: bool,
+    pub width: u32,
+    pub height: u32,
+    /// Level 0 first, each one the blocks of a level half the size.
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl CompressedImage {
+    /// Reads a KTX1 or DDS file, whichever the contents say it is.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let bytes = fs::read(path)?;
+        if bytes.starts_with(&KTX_MAGIC) {
+            parse_ktx(&bytes)
+        } else if bytes.starts_with(b"DDS ") {
+            parse_dds(&bytes)
+        } else {
+            Err(Error::TextureFile(format!("{} isn't a KTX or DDS file", path.display())))
+        }
+    }
+
+    /// The size of a mip level.
+    pub fn level_size(&self, level: usize) -> (u32, u32) {
+        ((self.width >> level).max(1), (self.height >> level).max(1))
+    }
+
+    /// Flips every level upside down by reordering rows of blocks and the
+    /// rows inside each block, so it matches how
+    /// [`Texture::load_file`](crate::texture::Texture::load_file) flips.
+    ///
+    /// That only works when each level's height is a multiple of 4 or less
+    /// than 4, otherwise nothing is changed and `false` is returned.
+    pub fn flip_vertical(&mut self) -> bool {
+        let flippable = (0..self.levels.len()).all(|l| {
+            let (_, h) = self.level_size(l);
+            h < 4 || h % 4 == 0
+        });
+        if !flippable {
+            return false;
+        }
+        for level in 0..self.levels.len() {
+            let (width, height) = self.level_size(level);
+            let block_bytes = self.format.block_bytes();
+            let row_bytes = width.div_ceil(4) as usize * block_bytes;
+            let rows = (height as usize).min(4);
+            let data = &mut self.levels[level];
+            let mut flipped = Vec::with_capacity(data.len());
+            for row in data.chunks(row_bytes).rev() {
+                for block in row.chunks(block_bytes) {
+                    flipped.extend(flip_block(self.format, block, rows));
+                }
+            }
+            *data = flipped;
+        }
+        true
+    }
+
+    /// Decodes one mip level. BC1 to BC3 come out RGBA, BC4 as one channel
+    /// and BC5 as two, like sampling them from the GPU would.
+    pub fn decompress(&self, level: usize) -> DynamicImage {
+        let (width, height) = self.level_size(level);
+        decompress(self.format, width, height, &self.levels[level])
+    }
+}
+
+const KTX_MAGIC: [u8; 12] = [0xAB, b'K', b'T', b'X', b' ', b'1', b'1', 0xBB, b'\r', b'\n', 0x1A, b'\n'];
+
+/// Reads little or big endian `u32`s, whichever the file was written in.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    big_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn u32(&mut self) -> Result<u32, Error> {
+        let word: [u8; 4] = self.take(4)?.try_into().unwrap();
+        Ok(if self.big_endian { u32::from_be_bytes(word) } else { u32::from_le_bytes(word) })
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.bytes.len());
+        match end {
+            Some(end) => {
+                let slice = &self.bytes[self.pos..end];
+                self.pos = end;
+                Ok(slice)
+            }
+            None => Err(Error::TextureFile("file ends early".to_string())),
+        }
+    }
+}
+
+/// Reads a [KTX1](https://registry.khronos.org/KTX/specs/1.0/ktxspec.v1.html)
+/// file holding one compressed 2D image.
+pub fn parse_ktx(bytes: &[u8]) -> Result<CompressedImage, Error> {
+    if !bytes.starts_with(&KTX_MAGIC) {
+        return Err(Error::TextureFile("not a KTX1 file".to_string()));
+    }
+    let mut r = Reader { bytes, pos: KTX_MAGIC.len(), big_endian: false };
+    r.big_endian = match r.u32()? {
+        0x0403_0201 => false,
+        0x0102_0304 => true,
+        _ => return Err(Error::TextureFile("bad KTX endianness marker".to_string())),
+    };
+    let _gl_type = r.u32()?;
+    let _gl_type_size = r.u32()?;
+    let _gl_format = r.u32()?;
+    let internal_format = r.u32()?;
+    let _base_internal_format = r.u32()?;
+    let width = r.u32()?;
+    let height = r.u32()?;
+    let depth = r.u32()?;
+    let array_elements = r.u32()?;
+    let faces = r.u32()?;
+    let mip_levels = r.u32()?.max(1);
+    let key_value_bytes = r.u32()?;
+
+    let (format, srgb) = BlockFormat::from_gl(internal_format)
+        .ok_or_else(|| Error::TextureFile(format!("unsupported KTX internal format 0x{:04X}", internal_format)))?;
+    if depth > 1 || array_elements > 0 || faces != 1 {
+        return Err(Error::TextureFile("only plain 2D KTX textures are supported".to_string()));
+    }
+    r.take(key_value_bytes as usize)?;
+    check_levels(width, height, mip_levels)?;
+
+    let mut levels = Vec::with_capacity(mip_levels as usize);
+    for level in 0..mip_levels {
+        let size = r.u32()? as usize;
+        let expected = format.level_bytes((width >> level).max(1), (height >> level).max(1));
+        if size != expected {
+            return Err(Error::TextureFile(format!("KTX level {} is {} bytes, expected {}", level, size, expected)));
+        }
+        levels.push(r.take(size)?.to_vec());
+        // levels are padded to 4 bytes
+        r.take((4 - size % 4) % 4)?;
+    }
+    Ok(CompressedImage { format, srgb, width, height, levels })
+}
+
+const DDSD_MIPMAPCOUNT: u32 = 0x2_0000;
+const DDPF_FOURCC: u32 = 0x4;
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_VOLUME: u32 = 0x20_0000;
+
+/// Reads a [DDS](https://learn.microsoft.com/en-us/windows/win32/direct3ddds/dx-graphics-dds-pguide)
+/// file holding one BC1 to BC5 2D image, with or without the DX10 header.
+pub fn parse_dds(bytes: &[u8]) -> Result<CompressedImage, Error> {
+    if !bytes.starts_with(b"DDS ") {
+        return Err(Error::TextureFile("not a DDS file".to_string()));
+    }
+    let mut r = Reader { bytes, pos: 4, big_endian: false };
+    if r.u32()? != 124 {
+        return Err(Error::TextureFile("bad DDS header size".to_string()));
+    }
+    let flags = r.u32()?;
+    let height = r.u32()?;
+    let width = r.u32()?;
+    let _pitch = r.u32()?;
+    let _depth = r.u32()?;
+    let mip_count = r.u32()?;
+    r.take(11 * 4)?;
+    let _pf_size = r.u32()?;
+    let pf_flags = r.u32()?;
+    let four_cc: [u8; 4] = r.take(4)?.try_into().unwrap();
+    r.take(5 * 4)?;
+    let _caps = r.u32()?;
+    let caps2 = r.u32()?;
+    r.take(3 * 4)?;
+
+    if caps2 & (DDSCAPS2_CUBEMAP | DDSCAPS2_VOLUME) != 0 {
+        return Err(Error::TextureFile("only plain 2D DDS textures are supported".to_string()));
+    }
+    if pf_flags & DDPF_FOURCC == 0 {
+        return Err(Error::TextureFile("DDS file isn't block compressed".to_string()));
+    }
+    let (format, srgb) = match &four_cc {
+        b"DXT1" => (BlockFormat::Bc1Alpha, false),
+        b"DXT2" | b"DXT3" => (BlockFormat::Bc2, false),
+        b"DXT4" | b"DXT5" => (BlockFormat::Bc3, false),
+        b"ATI1" | b"BC4U" => (BlockFormat::Bc4, false),
+        b"ATI2" | b"BC5U" => (BlockFormat::Bc5, false),
+        b"DX10" => {
+            let dxgi_format = r.u32()?;
+            let _dimension = r.u32()?;
+            let _misc = r.u32()?;
+            let array_size = r.u32()?;
+            let _misc2 = r.u32()?;
+            if array_size > 1 {
+                return Err(Error::TextureFile("DDS texture arrays aren't supported".to_string()));
+            }
+            match dxgi_format {
+                71 => (BlockFormat::Bc1Alpha, false),
+                72 => (BlockFormat::Bc1Alpha, true),
+                74 => (BlockFormat::Bc2, false),
+                75 => (BlockFormat::Bc2, true),
+                77 => (BlockFormat::Bc3, false),
+                78 => (BlockFormat::Bc3, true),
+                80 => (BlockFormat::Bc4, false),
+                83 => (BlockFormat::Bc5, false),
+                other => return Err(Error::TextureFile(format!("unsupported DXGI format {}", other))),
+            }
+        }
+        other => {
+            return Err(Error::TextureFile(format!(
+                "unsupported DDS format {}",
+                String::from_utf8_lossy(other)
+            )))
+        }
+    };
+
+    let mip_levels = if flags & DDSD_MIPMAPCOUNT != 0 { mip_count.max(1) } else { 1 };
+    check_levels(width, height, mip_levels)?;
+    let mut levels = Vec::with_capacity(mip_levels as usize);
+    for level in 0..mip_levels {
+        let size = format.level_bytes((width >> level).max(1), (height >> level).max(1));
+        levels.push(r.take(size)?.to_vec());
+    }
+    Ok(CompressedImage { format, srgb, width, height, levels })
+}
+
+/// Checks the size and mip level count from a file header before anything
+/// gets allocated for them. A full chain goes down to 1x1, so there are at
+/// most `floor(log2(max(width, height))) + 1` levels.
+fn check_levels(width: u32, height: u32, levels: u32) -> Result<(), Error> {
+    if width == 0 || height == 0 {
+        return Err(Error::TextureFile(format!("texture is {}x{}", width, height)));
+    }
+    let max = u32::BITS - width.max(height).leading_zeros();
+    if levels > max {
+        return Err(Error::TextureFile(format!("{} mip levels for a {}x{} texture, at most {} fit", levels, width, height, max)));
+    }
+    Ok(())
+}
+
+/// Flips the first `rows` rows of a block upside down.
+fn flip_block(format: BlockFormat, block: &[u8], rows: usize) -> Vec<u8> {
+    let mut out = block.to_vec();
+    match format {
+        BlockFormat::Bc1 | BlockFormat::Bc1Alpha => flip_color(&mut out[..8], rows),
+        BlockFormat::Bc2 => {
+            // 2 bytes of alpha per row
+            for row in 0..rows {
+                let from = rows - 1 - row;
+                out[row * 2..row * 2 + 2].copy_from_slice(&block[from * 2..from * 2 + 2]);
+            }
+            flip_color(&mut out[8..], rows);
+        }
+        BlockFormat::Bc3 => {
+            flip_alpha(&mut out[..8], rows);
+            flip_color(&mut out[8..], rows);
+        }
+        BlockFormat::Bc4 => flip_alpha(&mut out[..8], rows),
+        BlockFormat::Bc5 => {
+            flip_alpha(&mut out[..8], rows);
+            flip_alpha(&mut out[8..], rows);
+        }
+    }
+    out
+}
+
+/// BC1 color blocks have one byte of 2 bit indices per row.
+fn flip_color(block: &mut [u8], rows: usize) {
+    block[4..4 + rows].reverse();
+}
+
+/// BC4 style blocks have 12 bits of 3 bit indices per row.
+fn flip_alpha(block: &mut [u8], rows: usize) {
+    let bits = block[2..8].iter().rev().fold(0_u64, |acc, &b| (acc << 8) | b as u64);
+    let mut flipped = bits;
+    for row in 0..rows {
+        let from = rows - 1 - row;
+        flipped &= !(0xFFF << (row * 12));
+        flipped |= ((bits >> (from * 12)) & 0xFFF) << (row * 12);
+    }
+    for (i, byte) in block[2..8].iter_mut().enumerate() {
+        *byte = (flipped >> (i * 8)) as u8;
+    }
+}
+
+/// Decodes a whole level of blocks.
+pub fn decompress(format: BlockFormat, width: u32, height: u32, data: &[u8]) -> DynamicImage {
+    match format {
+        BlockFormat::Bc4 => DynamicImage::ImageLuma8(decode_blocks(format, width, height, data, |block| {
+            decode_alpha(block).map(|r| image::Luma([r]))
+        })),
+        BlockFormat::Bc5 => DynamicImage::ImageLumaA8(decode_blocks(format, width, height, data, |block| {
+            let (r, g) = (decode_alpha(&block[..8]), decode_alpha(&block[8..]));
+            std::array::from_fn(|i| image::LumaA([r[i], g[i]]))
+        })),
+        _ => DynamicImage::ImageRgba8(decode_blocks(format, width, height, data, |block| {
+            decode_rgba(format, block).map(image::Rgba)
+        })),
+    }
+}
+
+/// Decodes each block once and copies its texels into place, cutting off
+/// the parts of edge blocks past the image.
+fn decode_blocks<P: Pixel<Subpixel = u8>>(
+    format: BlockFormat,
+    width: u32,
+    height: u32,
+    data: &[u8],
+    decode: impl Fn(&[u8]) -> [P; 16],
+) -> ImageBuffer<P, Vec<u8>> {
+    let blocks_wide = width.div_ceil(4);
+    let blocks = (blocks_wide * height.div_ceil(4)) as usize;
+    let mut img = ImageBuffer::new(width, height);
+    for (i, block) in data.chunks_exact(format.block_bytes()).take(blocks).enumerate() {
+        let (block_x, block_y) = (i as u32 % blocks_wide * 4, i as u32 / blocks_wide * 4);
+        for (texel, pixel) in decode(block).into_iter().enumerate() {
+            let (x, y) = (block_x + texel as u32 % 4, block_y + texel as u32 / 4);
+            if x < width && y < height {
+                img.put_pixel(x, y, pixel);
+            }
+        }
+    }
+    img
+}
+
+fn decode_rgba(format: BlockFormat, block: &[u8]) -> [[u8; 4]; 16] {
+    let (color, alpha) = match format {
+        BlockFormat::Bc2 => {
+            let bits = u64::from_le_bytes(block[..8].try_into().unwrap());
+            let alpha: [u8; 16] = std::array::from_fn(|i| ((bits >> (i * 4)) & 0xF) as u8 * 17);
+            (&block[8..], Some(alpha))
+        }
+        BlockFormat::Bc3 => (&block[8..], Some(decode_alpha(&block[..8]))),
+        _ => (block, None),
+    };
+    let mut out = decode_color(color, format);
+    if let Some(alpha) = alpha {
+        for (texel, a) in out.iter_mut().zip(alpha) {
+            texel[3] = a;
+        }
+    }
+    out
+}
+
+/// A BC1 color block, also the color of BC2 and BC3. For both BC1 formats
+/// `c0 <= c1` switches to 3 colors plus black, which is transparent for
+/// [`BlockFormat::Bc1Alpha`]. BC2 and BC3 always interpolate 4 colors.
+fn decode_color(block: &[u8], format: BlockFormat) -> [[u8; 4]; 16] {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let rgb = |c: u16| {
+        let (r, g, b) = ((c >> 11) & 0x1F, (c >> 5) & 0x3F, c & 0x1F);
+        [(r * 255 / 31) as u32, (g * 255 / 63) as u32, (b * 255 / 31) as u32]
+    };
+    let (a, b) = (rgb(c0), rgb(c1));
+    let mix = |wa: u32, wb: u32| {
+        let m = |i: usize| ((a[i] * wa + b[i] * wb) / (wa + wb)) as u8;
+        [m(0), m(1), m(2), 255]
+    };
+    let palette = match format {
+        BlockFormat::Bc1 if c0 <= c1 => [mix(1, 0), mix(0, 1), mix(1, 1), [0, 0, 0, 255]],
+        BlockFormat::Bc1Alpha if c0 <= c1 => [mix(1, 0), mix(0, 1), mix(1, 1), [0, 0, 0, 0]],
+        _ => [mix(1, 0), mix(0, 1), mix(2, 1), mix(1, 2)],
+    };
+    let indices = u32::from_le_bytes(block[4..8].try_into().unwrap());
+    std::array::from_fn(|i| palette[((indices >> (i * 2)) & 3) as usize])
+}
+
+/// A BC4 block, also the alpha of BC3 and each channel of BC5.
+fn decode_alpha(block: &[u8]) -> [u8; 16] {
+    let (a0, a1) = (block[0] as u32, block[1] as u32);
+    let palette: [u8; 8] = std::array::from_fn(|i| {
+        let i = i as u32;
+        match i {
+            0 => a0 as u8,
+            1 => a1 as u8,
+            _ if a0 > a1 => ((a0 * (8 - i) + a1 * (i - 1)) / 7) as u8,
+            6 => 0,
+            7 => 255,
+            _ => ((a0 * (6 - i) + a1 * (i - 1)) / 5) as u8,
+        }
+    });
+    let bits = block[2..8].iter().rev().fold(0_u64, |acc, &b| (acc << 8) | b as u64);
+    std::array::from_fn(|i| palette[((bits >> (i * 3)) & 7) as usize])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Blue `c0`, red `c1` and texels 0 to 3 using indices 0 to 3, so
+    /// `c0 < c1`.
+    const THREE_COLOR_BLOCK: [u8; 8] = [0x1F, 0x00, 0x00, 0xF8, 0b11_10_01_00, 0, 0, 0];
+
+    #[test]
+    fn bc1_three_color_mode_has_opaque_black() {
+        let texels = decode_rgba(BlockFormat::Bc1, &THREE_COLOR_BLOCK);
+        assert_eq!(texels[..4], [[0, 0, 255, 255], [255, 0, 0, 255], [127, 0, 127, 255], [0, 0, 0, 255]]);
+        let img = decompress(BlockFormat::Bc1, 4, 4, &THREE_COLOR_BLOCK).to_rgba8();
+        assert_eq!(img.get_pixel(3, 0).0, [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn bc1_alpha_three_color_mode_has_transparent_black() {
+        let texels = decode_rgba(BlockFormat::Bc1Alpha, &THREE_COLOR_BLOCK);
+        assert_eq!(texels[..4], [[0, 0, 255, 255], [255, 0, 0, 255], [127, 0, 127, 255], [0, 0, 0, 0]]);
+    }
+
+    #[test]
+    fn bc1_four_color_mode() {
+        // the same colors swapped, so c0 > c1
+        let block = [0x00, 0xF8, 0x1F, 0x00, 0b11_10_01_00, 0, 0, 0];
+        for format in [BlockFormat::Bc1, BlockFormat::Bc1Alpha] {
+            let texels = decode_rgba(format, &block);
+            assert_eq!(texels[..4], [[255, 0, 0, 255], [0, 0, 255, 255], [170, 0, 85, 255], [85, 0, 170, 255]]);
+        }
+    }
+
+    /// A little endian KTX1 header for a BC1 texture, without any levels.
+    fn ktx_header(width: u32, height: u32, mip_levels: u32) -> Vec<u8> {
+        let mut bytes = KTX_MAGIC.to_vec();
+        let fields = [0x0403_0201, 0, 1, 0, GL_COMPRESSED_RGB_S3TC_DXT1_EXT.0, GL_RGB.0, width, height, 0, 0, 1, mip_levels, 0];
+        for field in fields {
+            bytes.extend_from_slice(&field.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn ktx_levels() {
+        let mut bytes = ktx_header(4, 4, 1);
+        bytes.extend_from_slice(&8u32.to_le_bytes());
+        bytes.extend_from_slice(&THREE_COLOR_BLOCK);
+        let img = parse_ktx(&bytes).unwrap();
+        assert_eq!((img.format, img.width, img.height, img.levels.len()), (BlockFormat::Bc1, 4, 4, 1));
+    }
+
+    #[test]
+    fn ktx_with_too_many_levels_is_an_error() {
+        // 4x4 has 3 levels at most
+        assert!(matches!(parse_ktx(&ktx_header(4, 4, 4)), Err(Error::TextureFile(_))));
+        assert!(matches!(parse_ktx(&ktx_header(4, 4, u32::MAX)), Err(Error::TextureFile(_))));
+        assert!(matches!(parse_ktx(&ktx_header(0, 4, 1)), Err(Error::TextureFile(_))));
+    }
+
+    #[test]
+    fn truncated_files_are_errors() {
+        let mut bytes = ktx_header(8, 8, 4);
+        bytes.extend_from_slice(&32u32.to_le_bytes());
+        bytes.extend_from_slice(&[0; 10]);
+        assert!(matches!(parse_ktx(&bytes), Err(Error::TextureFile(_))));
+        assert!(matches!(parse_ktx(&bytes[..30]), Err(Error::TextureFile(_))));
+
+        let mut dds = b"DDS ".to_vec();
+        let mut header = [0u32; 31];
+        header[0] = 124;
+        header[1] = DDSD_MIPMAPCOUNT;
+        header[2] = 4;
+        header[3] = 4;
+        header[6] = u32::MAX;
+        header[19] = DDPF_FOURCC;
+        header[20] = u32::from_le_bytes(*b"DXT1");
+        for field in header {
+            dds.extend_from_slice(&field.to_le_bytes());
+        }
+        assert!(matches!(parse_dds(&dds), Err(Error::TextureFile(_))));
+        header[6] = 1;
+        dds.truncate(4);
+        for field in header {
+            dds.extend_from_slice(&field.to_le_bytes());
+        }
+        assert!(matches!(parse_dds(&dds), Err(Error::TextureFile(_))));
+        dds.extend_from_slice(&THREE_COLOR_BLOCK);
+        assert_eq!(parse_dds(&dds).unwrap().levels.len(), 1);
+    }
+
+    #[test]
+    fn decompress_cuts_off_partial_blocks() {
+        // 2x2 blocks for a 6x5 image, the second block is the first one
+        // with c0 and c1 swapped
+        let mut data = Vec::new();
+        for i in 0..4 {
+            let mut block = THREE_COLOR_BLOCK;
+            if i % 2 == 1 {
+                block[..4].copy_from_slice(&[0x00, 0xF8, 0x1F, 0x00]);
+            }
+            data.extend_from_slice(&block);
+        }
+        let img = decompress(BlockFormat::Bc1, 6, 5, &data).to_rgba8();
+        assert_eq!(img.dimensions(), (6, 5));
+        for y in 0..5 {
+            for x in 0..6 {
+                let block = ((y / 4) * 2 + x / 4) as usize;
+                let texels = decode_rgba(BlockFormat::Bc1, &data[block * 8..block * 8 + 8]);
+                assert_eq!(img.get_pixel(x, y).0, texels[(y % 4 * 4 + x % 4) as usize]);
+            }
+        }
+    }
+
+    #[test]
+    fn bc3_color_always_has_four_colors() {
+        // fully opaque alpha block followed by the c0 < c1 color block
+        let mut block = [255, 255, 0, 0, 0, 0, 0, 0].to_vec();
+        block.extend_from_slice(&THREE_COLOR_BLOCK);
+        let texels = decode_rgba(BlockFormat::Bc3, &block);
+        assert_eq!(texels[..4], [[0, 0, 255, 255], [255, 0, 0, 255], [85, 0, 170, 255], [170, 0, 85, 255]]);
+    }
+}
@@ -0,0 +1,211 @@
+use std::f32::consts::PI;
+use std::path::Path;
+
+use gl33::{*, global_loader::*};
+use image::{DynamicImage, GenericImageView, ImageReader, Rgba32FImage};
+
+use super::Error;
+use super::format;
+use super::sampler::TextureParams;
+use super::texture::{mip_chain, upload_level, LoadOptions, Mipmaps};
+
+/// The cube map face targets in the order GL numbers them: +X, -X, +Y, -Y,
+/// +Z, -Z. Every method that takes six faces takes them in this order.
+pub const FACES: [GLenum; 6] = [
+    GL_TEXTURE_CUBE_MAP_POSITIVE_X,
+    GL_TEXTURE_CUBE_MAP_NEGATIVE_X,
+    GL_TEXTURE_CUBE_MAP_POSITIVE_Y,
+    GL_TEXTURE_CUBE_MAP_NEGATIVE_Y,
+    GL_TEXTURE_CUBE_MAP_POSITIVE_Z,
+    GL_TEXTURE_CUBE_MAP_NEGATIVE_Z,
+];
+
+/// A `GL_TEXTURE_CUBE_MAP` texture, sampled with a direction instead of a
+/// coordinate. Use it with a `samplerCube` uniform.
+///
+/// Unlike [`Texture`](crate::texture::Texture) the images aren't flipped, cube
+/// maps expect the first row at the top.
+pub struct CubeMap(pub u32);
+impl CubeMap {
+    pub fn new() -> Result<Self, Error> {
+        let mut tex = 0;
+        unsafe { glGenTextures(1, &mut tex) };
+        if tex != 0 {
+            // filter across face edges instead of showing seams
+            unsafe { glEnable(GL_TEXTURE_CUBE_MAP_SEAMLESS) };
+            Ok(Self(tex))
+        } else {
+            Err(Error::Alloc("cube map"))
+        }
+    }
+
+    pub fn bind(&self, tex_unit: GLenum) {
+        unsafe {
+            glActiveTexture(tex_unit);
+            glBindTexture(GL_TEXTURE_CUBE_MAP, self.0);
+        }
+    }
+
+    /// Sets how the cube map gets sampled. Skyboxes want
+    /// [`Wrap::ClampToEdge`](crate::sampler::Wrap::ClampToEdge). Binds it to
+    /// the active texture unit.
+    pub fn set_params(&self, params: &TextureParams) {
+        unsafe { glBindTexture(GL_TEXTURE_CUBE_MAP, self.0) };
+        params.apply(GL_TEXTURE_CUBE_MAP);
+    }
+
+    /// Loads six image files, one per face in [`FACES`] order.
+    pub fn load_faces(&self, paths: &[impl AsRef<Path>; 6], options: &LoadOptions) -> Result<(), Error> {
+        let mut faces = Vec::with_capacity(6);
+        for path in paths {
+            faces.push(ImageReader::open(path)?.decode()?);
+        }
+        self.load_face_images(&faces, options)
+    }
+
+    /// Loads one image laid out as a cross, either 4 faces wide and 3 tall:
+    ///
+    /// ```text
+    ///     +Y
+    /// -X  +Z  +X  -Z
+    ///     -Y
+    /// ```
+    ///
+    /// or 3 wide and 4 tall, with -Z upside down under -Y.
+    pub fn load_cross(&self, path: impl AsRef<Path>, options: &LoadOptions) -> Result<(), Error> {
+        let img = ImageReader::open(path)?.decode()?;
+        self.load_face_images(&cross_faces(&img)?, options)
+    }
+
+    /// Loads an equirectangular (latitude/longitude) panorama, converting it
+    /// to six `face_size` square faces on the CPU.
+    pub fn load_equirect(&self, path: impl AsRef<Path>, face_size: u32, options: &LoadOptions) -> Result<(), Error> {
+        let img = ImageReader::open(path)?.decode()?;
+        self.load_face_images(&equirect_faces(&img, face_size), options)
+    }
+
+    /// Uploads six images, one per face in [`FACES`] order, and makes the mip
+    /// levels `options` asks for. Faces have to be square and the same size.
+    pub fn load_face_images(&self, faces: &[DynamicImage], options: &LoadOptions) -> Result<(), Error> {
+        let size = match faces {
+            [first, ..] if faces.len() == 6 => first.width(),
+            _ => return Err(Error::TextureSize(format!("a cube map needs 6 faces, got {}", faces.len()))),
+        };
+        if let Some(face) = faces.iter().find(|f| f.width() != size || f.height() != size) {
+            return Err(Error::TextureSize(format!(
+                "cube map faces must all be {0}x{0}, got {1}x{2}",
+                size,
+                face.width(),
+                face.height()
+            )));
+        }
+
+        unsafe { glBindTexture(GL_TEXTURE_CUBE_MAP, self.0) };
+        let mut levels = 1;
+        for (&target, face) in FACES.iter().zip(faces) {
+            match options.mipmaps {
+                Mipmaps::Cpu(filter) => {
+                    let chain = mip_chain(face, filter);
+                    levels = chain.len();
+                    for (level, img) in chain.iter().enumerate() {
+                        upload_level(target, level as i32, img, options.color_space)?;
+                    }
+                }
+                _ => upload_level(target, 0, face, options.color_space)?,
+            }
+        }
+        unsafe {
+            glTexParameteri(GL_TEXTURE_CUBE_MAP, GL_TEXTURE_BASE_LEVEL, 0);
+            if options.mipmaps == Mipmaps::Generate {
+                glTexParameteri(GL_TEXTURE_CUBE_MAP, GL_TEXTURE_MAX_LEVEL, 1000);
+                glGenerateMipmap(GL_TEXTURE_CUBE_MAP);
+            } else {
+                glTexParameteri(GL_TEXTURE_CUBE_MAP, GL_TEXTURE_MAX_LEVEL, levels as i32 - 1);
+            }
+        }
+        if let Some(swizzle) = format::gray_swizzle(faces[0].color()).filter(|_| options.gray_swizzle) {
+            format::set_swizzle(GL_TEXTURE_CUBE_MAP, swizzle);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for CubeMap {
+    fn drop(&mut self) {
+        unsafe { glDeleteTextures(1, &self.0) };
+    }
+}
+
+/// Cuts the six faces out of a cross layout, see [`CubeMap::load_cross`].
+pub fn cross_faces(img: &DynamicImage) -> Result<[DynamicImage; 6], Error> {
+    let (width, height) = img.dimensions();
+    let face = |col: u32, row: u32, size: u32| img.crop_imm(col * size, row * size, size, size);
+    if width * 3 == height * 4 {
+        let size = width / 4;
+        Ok([face(2, 1, size), face(0, 1, size), face(1, 0, size), face(1, 2, size), face(1, 1, size), face(3, 1, size)])
+    } else if width * 4 == height * 3 {
+        let size = width / 3;
+        Ok([face(2, 1, size), face(0, 1, size), face(1, 0, size), face(1, 2, size), face(1, 1, size), face(1, 3, size).rotate180()])
+    } else {
+        Err(Error::TextureSize(format!(
+            "a cube map cross must be 4:3 or 3:4, got {}x{}",
+            width, height
+        )))
+    }
+}
+
+/// Resamples an equirectangular panorama into six square faces, see
+/// [`CubeMap::load_equirect`]. 8 bit images stay 8 bit, so sRGB still works.
+pub fn equirect_faces(img: &DynamicImage, face_size: u32) -> [DynamicImage; 6] {
+    let src = img.to_rgba32f();
+    let to_output = |face: Rgba32FImage| {
+        let face = DynamicImage::ImageRgba32F(face);
+        match img {
+            DynamicImage::ImageLuma8(_)
+            | DynamicImage::ImageLumaA8(_)
+            | DynamicImage::ImageRgb8(_)
+            | DynamicImage::ImageRgba8(_) => DynamicImage::ImageRgba8(face.to_rgba8()),
+            _ => face,
+        }
+    };
+    std::array::from_fn(|i| {
+        let face = Rgba32FImage::from_fn(face_size, face_size, |x, y| {
+            // -1..1 across the face, through the middle of each texel
+            let s = 2.0 * (x as f32 + 0.5) / face_size as f32 - 1.0;
+            let t = 2.0 * (y as f32 + 0.5) / face_size as f32 - 1.0;
+            // the major axis table from the GL spec, solved for the direction
+            let [dx, dy, dz] = match i {
+                0 => [1.0, -t, -s],
+                1 => [-1.0, -t, s],
+                2 => [s, 1.0, t],
+                3 => [s, -1.0, -t],
+                4 => [s, -t, 1.0],
+                _ => [-s, -t, -1.0],
+            };
+            let len = (dx * dx + dy * dy + dz * dz).sqrt();
+            let u = 0.5 + dx.atan2(-dz) / (2.0 * PI);
+            let v = (dy / len).acos() / PI;
+            image::Rgba(sample_bilinear(&src, u, v))
+        });
+        to_output(face)
+    })
+}
+
+/// Samples with `u` wrapping around and `v` clamped, like a panorama.
+fn sample_bilinear(img: &Rgba32FImage, u: f32, v: f32) -> [f32; 4] {
+    let (width, height) = img.dimensions();
+    let x = u * width as f32 - 0.5;
+    let y = (v * height as f32 - 0.5).clamp(0.0, height as f32 - 1.0);
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let wrap = |x: f32| (x as i64).rem_euclid(width as i64) as u32;
+    let clamp = |y: f32| (y as u32).min(height - 1);
+    let texel = |x: f32, y: f32| img.get_pixel(wrap(x), clamp(y)).0;
+    let (a, b) = (texel(x0, y0), texel(x0 + 1.0, y0));
+    let (c, d) = (texel(x0, y0 + 1.0), texel(x0 + 1.0, y0 + 1.0));
+    std::array::from_fn(|i| {
+        let top = a[i] + (b[i] - a[i]) * fx;
+        let bottom = c[i] + (d[i] - c[i]) * fx;
+        top + (bottom - top) * fy
+    })
+}
@@ -0,0 +1,280 @@
+use std::fmt;
+use std::path::PathBuf;
+
+/// How bad a single compiler message is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl Severity {
+    fn parse(word: &str) -> Option<Self> {
+        match word.to_ascii_lowercase().as_str() {
+            "error" | "fatal error" => Some(Severity::Error),
+            "warning" => Some(Severity::Warning),
+            "info" | "note" => Some(Severity::Info),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Info => write!(f, "info"),
+        }
+    }
+}
+
+/// One message out of a shader info log.
+///
+/// `file` is the GLSL source string number (0 unless `#line` says otherwise),
+/// `line` and `column` are 1-based like the driver reports them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub file: Option<u32>,
+    /// The file `file` refers to, when the shader was preprocessed.
+    pub path: Option<PathBuf>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    pub severity: Severity,
+    pub message: String,
+    /// The offending source line with a caret under it, if it could be found.
+    pub excerpt: Option<String>,
+}
+
+impl Diagnostic {
+    fn unlocated(severity: Severity, message: &str) -> Self {
+        Self {
+            file: None,
+            path: None,
+            line: None,
+            column: None,
+            severity,
+            message: message.trim().to_string(),
+            excerpt: None,
+        }
+    }
+
+    /// Renders the line this diagnostic points at out of `source`, with a
+    /// caret under the column (or the whole line when there's no column).
+    pub fn render_excerpt(&self, source: &str) -> Option<String> {
+        let line_no = self.line?;
+        let text = source.lines().nth(line_no.checked_sub(1)? as usize)?;
+        let gutter = line_no.to_string().len().max(4);
+        let marker = match self.column {
+            Some(col) => {
+                // keep tabs so the caret lines up with the source line
+                let pad: String = text
+                    .chars()
+                    .take(col.saturating_sub(1) as usize)
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect();
+                format!("{}^", pad)
+            }
+            None => {
+                let indent = text.len() - text.trim_start().len();
+                let width = text.trim().len().max(1);
+                format!("{}{}", &text[..indent], "^".repeat(width))
+            }
+        };
+        Some(format!(
+            "{:>gutter$} | {}\n{:>gutter$} | {}",
+            line_no,
+            text,
+            "",
+            marker,
+            gutter = gutter
+        ))
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let file = match (&self.path, self.file) {
+            (Some(path), _) => Some(path.display().to_string()),
+            (None, file) => file.map(|n| n.to_string()),
+        };
+        match (file, self.line, self.column) {
+            (Some(file), Some(line), Some(col)) => write!(f, "{}:{}:{}: ", file, line, col)?,
+            (Some(file), Some(line), None) => write!(f, "{}:{}: ", file, line)?,
+            _ => (),
+        }
+        write!(f, "{}: {}", self.severity, self.message)?;
+        if let Some(excerpt) = &self.excerpt {
+            write!(f, "\n{}", excerpt)?;
+        }
+        Ok(())
+    }
+}
+
+/// Splits the leading run of digits off of `s`.
+fn split_number(s: &str) -> Option<(u32, &str)> {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    if end == 0 {
+        return None;
+    }
+    Some((s[..end].parse().ok()?, &s[end..]))
+}
+
+/// Splits `severity[ code]: message` into the severity and the message.
+fn split_severity(s: &str) -> Option<(Severity, &str)> {
+    let (head, message) = s.split_once(':')?;
+    // longest first, so `fatal error C9999` isn't read as just `fatal`
+    let head = head.trim().to_ascii_lowercase();
+    let word = ["fatal error", "error", "warning", "info", "note"]
+        .into_iter()
+        .find(|word| head.strip_prefix(word).is_some_and(|rest| rest.is_empty() || rest.starts_with(' ')))?;
+    Some((Severity::parse(word)?, message.trim()))
+}
+
+/// Mesa: `0:12(5): error: message`
+fn parse_mesa(line: &str) -> Option<Diagnostic> {
+    let (file, rest) = split_number(line)?;
+    let (line_no, rest) = split_number(rest.strip_prefix(':')?)?;
+    let (column, rest) = split_number(rest.strip_prefix('(')?)?;
+    let rest = rest.strip_prefix("):")?;
+    let (severity, message) = split_severity(rest.trim_start())?;
+    Some(Diagnostic {
+        file: Some(file),
+        path: None,
+        line: Some(line_no),
+        column: Some(column),
+        severity,
+        message: message.to_string(),
+        excerpt: None,
+    })
+}
+
+/// NVIDIA: `0(12) : error C0000: message`
+fn parse_nvidia(line: &str) -> Option<Diagnostic> {
+    let (file, rest) = split_number(line)?;
+    let (line_no, rest) = split_number(rest.strip_prefix('(')?)?;
+    let rest = rest.strip_prefix(')')?.trim_start().strip_prefix(':')?;
+    let (severity, message) = split_severity(rest.trim_start())?;
+    Some(Diagnostic {
+        file: Some(file),
+        path: None,
+        line: Some(line_no),
+        column: None,
+        severity,
+        message: message.to_string(),
+        excerpt: None,
+    })
+}
+
+/// AMD, Intel and Apple: `ERROR: 0:12: message`
+fn parse_amd(line: &str) -> Option<Diagnostic> {
+    let (head, rest) = line.split_once(':')?;
+    let severity = Severity::parse(head.trim())?;
+    let rest = rest.trim_start();
+    match split_number(rest).and_then(|(file, r)| Some((file, split_number(r.strip_prefix(':')?)?))) {
+        Some((file, (line_no, message))) => Some(Diagnostic {
+            file: Some(file),
+            path: None,
+            line: Some(line_no),
+            column: None,
+            severity,
+            message: message.trim_start_matches(':').trim().to_string(),
+            excerpt: None,
+        }),
+        None => Some(Diagnostic::unlocated(severity, rest)),
+    }
+}
+
+/// Parses a driver info log into diagnostics.
+///
+/// Understands the Mesa, NVIDIA and AMD/Intel style logs. Lines that don't
+/// match any of them are glued onto the previous diagnostic, or become an
+/// unlocated error if they come first.
+pub fn parse_info_log(log: &str) -> Vec<Diagnostic> {
+    let mut out: Vec<Diagnostic> = Vec::new();
+    for line in log.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        let parsed = parse_mesa(trimmed)
+            .or_else(|| parse_nvidia(trimmed))
+            .or_else(|| parse_amd(trimmed));
+        match (parsed, out.last_mut()) {
+            (Some(d), _) => out.push(d),
+            (None, Some(prev)) => {
+                prev.message.push('\n');
+                prev.message.push_str(trimmed);
+            }
+            (None, None) => out.push(Diagnostic::unlocated(Severity::Error, trimmed)),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "#version 330 core\nvoid main() {\n    gl_Position = vec4(pos, 1.0);\n}\n";
+
+    #[test]
+    fn mesa() {
+        let d = parse_info_log("0:3(25): error: `pos' undeclared\n");
+        assert_eq!(d.len(), 1);
+        assert_eq!((d[0].file, d[0].line, d[0].column), (Some(0), Some(3), Some(25)));
+        assert_eq!(d[0].severity, Severity::Error);
+        assert_eq!(d[0].message, "`pos' undeclared");
+        assert_eq!(
+            d[0].render_excerpt(SOURCE).unwrap(),
+            "   3 |     gl_Position = vec4(pos, 1.0);\n     |                         ^"
+        );
+    }
+
+    #[test]
+    fn nvidia() {
+        let d = parse_info_log("0(3) : error C1008: undefined variable \"pos\"\n");
+        assert_eq!(d.len(), 1);
+        assert_eq!((d[0].file, d[0].line, d[0].column), (Some(0), Some(3), None));
+        assert_eq!(d[0].severity, Severity::Error);
+        assert_eq!(d[0].message, "undefined variable \"pos\"");
+        assert_eq!(
+            d[0].render_excerpt(SOURCE).unwrap(),
+            "   3 |     gl_Position = vec4(pos, 1.0);\n     |     ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^"
+        );
+    }
+
+    #[test]
+    fn nvidia_fatal_error() {
+        let d = parse_info_log("0(1) : fatal error C9999: too many errors, stopping\n");
+        assert_eq!(d.len(), 1);
+        assert_eq!((d[0].file, d[0].line), (Some(0), Some(1)));
+        assert_eq!(d[0].severity, Severity::Error);
+        assert_eq!(d[0].message, "too many errors, stopping");
+    }
+
+    #[test]
+    fn amd() {
+        let d = parse_info_log("WARNING: 0:2: '' : extension not supported\nERROR: 0:3: 'pos' : undeclared identifier\n");
+        assert_eq!(d.len(), 2);
+        assert_eq!(d[0].severity, Severity::Warning);
+        assert_eq!((d[1].file, d[1].line, d[1].column), (Some(0), Some(3), None));
+        assert_eq!(d[1].severity, Severity::Error);
+        assert_eq!(d[1].message, "'pos' : undeclared identifier");
+        assert_eq!(
+            d[1].render_excerpt(SOURCE).unwrap(),
+            "   3 |     gl_Position = vec4(pos, 1.0);\n     |     ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^"
+        );
+    }
+
+    #[test]
+    fn continuation_lines() {
+        let d = parse_info_log("link failed\n0:1(1): warning: w\n  more about w\n");
+        assert_eq!(d.len(), 2);
+        assert_eq!(d[0].line, None);
+        assert_eq!(d[0].severity, Severity::Error);
+        assert_eq!(d[1].severity, Severity::Warning);
+        assert_eq!(d[1].message, "w\nmore about w");
+    }
+}
@@ -0,0 +1,162 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use gl33::GLenum;
+
+use crate::diagnostic::Diagnostic;
+use crate::shader::ShaderType;
+
+/// Everything that can go wrong while creating GL objects or loading the
+/// files that feed them.
+#[derive(Debug)]
+pub enum Error {
+    /// GL (or SDL) refused to hand out a new object, the string says which kind.
+    Alloc(&'static str),
+    /// SDL failed while setting up the context or window.
+    Sdl(String),
+    /// Reading a file from disk failed.
+    Io(io::Error),
+    /// An image file couldn't be decoded.
+    Image(image::ImageError),
+    /// The image decoded fine but there's no GL format for its pixels.
+    UnsupportedFormat(image::ColorType),
+    /// The images don't have the sizes the texture needs, eg cube map faces
+    /// that aren't square.
+    TextureSize(String),
+    /// A KTX or DDS file is malformed or holds something that can't be loaded.
+    TextureFile(String),
+    /// Two images were added to an
+    /// [`AtlasBuilder`](crate::atlas::AtlasBuilder) under the same name.
+    DuplicateAtlasName(String),
+    /// A JSON file, eg an atlas layout, couldn't be read or written.
+    Json(serde_json::Error),
+    /// The shader preprocessor couldn't expand a file.
+    Preprocess {
+        file: PathBuf,
+        line: usize,
+        message: String,
+    },
+    /// A shader stage failed to compile, holds the driver's info log and the
+    /// diagnostics parsed out of it.
+    Compile {
+        stage: ShaderType,
+        log: String,
+        diagnostics: Vec<Diagnostic>,
+    },
+    /// The program failed to link, holds the driver's info log.
+    Link(String),
+    /// A range of elements goes past the end of a
+    /// [`Buffer`](crate::buffer::Buffer).
+    BufferRange {
+        start: usize,
+        end: usize,
+        len: usize,
+    },
+    /// A vertex layout doesn't fit the program's inputs, or describes
+    /// something GL can't do.
+    VertexLayout(String),
+    /// The named uniform isn't an active uniform of the program.
+    MissingUniform(String),
+    /// Every texture unit was taken before the named sampler got one, see
+    /// [`TextureUnits`](crate::texture_unit::TextureUnits).
+    OutOfTextureUnits {
+        sampler: String,
+        max: u32,
+    },
+    /// The named uniform block isn't an active block of the program.
+    MissingUniformBlock(String),
+    /// The value's GLSL type doesn't match the type of the uniform.
+    UniformType {
+        name: String,
+        expected: GLenum,
+        found: GLenum,
+    },
+    /// More array elements were given than the uniform has.
+    UniformSize {
+        name: String,
+        len: usize,
+        size: i32,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Alloc(what) => write!(f, "Couldn't allocate a new {}", what),
+            Error::Sdl(msg) => write!(f, "SDL Error: {}", msg),
+            Error::Io(e) => write!(f, "IO Error: {}", e),
+            Error::Image(e) => write!(f, "Image Error: {}", e),
+            Error::UnsupportedFormat(color) => write!(f, "Not supported Image format: {:?}", color),
+            Error::TextureSize(message) => write!(f, "Texture Size Error: {}", message),
+            Error::TextureFile(message) => write!(f, "Texture File Error: {}", message),
+            Error::DuplicateAtlasName(name) => write!(f, "Atlas image name used twice: {}", name),
+            Error::Json(e) => write!(f, "JSON Error: {}", e),
+            Error::Preprocess { file, line, message } => {
+                write!(f, "Preprocess Error: {}:{}: {}", file.display(), line, message)
+            }
+            Error::Compile { stage, log, diagnostics } => {
+                write!(f, "{:?} Compile Error:", stage)?;
+                if diagnostics.is_empty() {
+                    return write!(f, " {}", log);
+                }
+                for d in diagnostics {
+                    write!(f, "\n{}", d)?;
+                }
+                Ok(())
+            }
+            Error::Link(log) => write!(f, "Program Link Error: {}", log),
+            Error::BufferRange { start, end, len } => {
+                write!(f, "Buffer range {}..{} is out of bounds for {} elements", start, end, len)
+            }
+            Error::VertexLayout(message) => write!(f, "Vertex Layout Error: {}", message),
+            Error::MissingUniform(name) => write!(f, "Uniform not found: {}", name),
+            Error::OutOfTextureUnits { sampler, max } => {
+                write!(f, "No texture unit left for {}, all {} are in use", sampler, max)
+            }
+            Error::MissingUniformBlock(name) => write!(f, "Uniform block not found: {}", name),
+            Error::UniformType { name, expected, found } => {
+                write!(f, "Uniform {} has type {:?} but was given a {:?}", name, expected, found)
+            }
+            Error::UniformSize { name, len, size } => {
+                write!(f, "Uniform {} has {} elements but was given {}", name, size, len)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Image(e) => Some(e),
+            Error::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<image::ImageError> for Error {
+    fn from(e: image::ImageError) -> Self {
+        Error::Image(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
+
+#[cfg(feature = "window")]
+impl From<beryllium::error::SdlError> for Error {
+    fn from(e: beryllium::error::SdlError) -> Self {
+        Error::Sdl(format!("{:?}", e))
+    }
+}
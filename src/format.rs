@@ -0,0 +1,109 @@
+//! How image pixels map onto GL texture formats.
+
+use gl33::{*, global_loader::*};
+use image::ColorType;
+
+use super::Error;
+
+/// The internal format, format and type triple `glTexImage*` takes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelFormat {
+    /// What the GPU stores.
+    pub internal_format: GLenum,
+    /// Which channels the uploaded data has.
+    pub format: GLenum,
+    /// The type of each channel in the uploaded data.
+    pub ty: GLenum,
+    pub bytes_per_pixel: usize,
+}
+
+impl PixelFormat {
+    pub const R8: Self = Self::new(GL_R8, GL_RED, GL_UNSIGNED_BYTE, 1);
+    pub const RG8: Self = Self::new(GL_RG8, GL_RG, GL_UNSIGNED_BYTE, 2);
+    pub const RGB8: Self = Self::new(GL_RGB8, GL_RGB, GL_UNSIGNED_BYTE, 3);
+    pub const RGBA8: Self = Self::new(GL_RGBA8, GL_RGBA, GL_UNSIGNED_BYTE, 4);
+    pub const R16: Self = Self::new(GL_R16, GL_RED, GL_UNSIGNED_SHORT, 2);
+    pub const RG16: Self = Self::new(GL_RG16, GL_RG, GL_UNSIGNED_SHORT, 4);
+    pub const RGB16: Self = Self::new(GL_RGB16, GL_RGB, GL_UNSIGNED_SHORT, 6);
+    pub const RGBA16: Self = Self::new(GL_RGBA16, GL_RGBA, GL_UNSIGNED_SHORT, 8);
+    pub const R32F: Self = Self::new(GL_R32F, GL_RED, GL_FLOAT, 4);
+    pub const RG32F: Self = Self::new(GL_RG32F, GL_RG, GL_FLOAT, 8);
+    pub const RGB32F: Self = Self::new(GL_RGB32F, GL_RGB, GL_FLOAT, 12);
+    pub const RGBA32F: Self = Self::new(GL_RGBA32F, GL_RGBA, GL_FLOAT, 16);
+
+    const fn new(internal_format: GLenum, format: GLenum, ty: GLenum, bytes_per_pixel: usize) -> Self {
+        Self { internal_format, format, ty, bytes_per_pixel }
+    }
+
+    /// The format that keeps every bit of an image's pixels. Luma images
+    /// become one or two channel textures, see [`gray_swizzle`].
+    pub fn of(color: ColorType) -> Result<Self, Error> {
+        Ok(match color {
+            ColorType::L8 => Self::R8,
+            ColorType::La8 => Self::RG8,
+            ColorType::Rgb8 => Self::RGB8,
+            ColorType::Rgba8 => Self::RGBA8,
+            ColorType::L16 => Self::R16,
+            ColorType::La16 => Self::RG16,
+            ColorType::Rgb16 => Self::RGB16,
+            ColorType::Rgba16 => Self::RGBA16,
+            ColorType::Rgb32F => Self::RGB32F,
+            ColorType::Rgba32F => Self::RGBA32F,
+            other => return Err(Error::UnsupportedFormat(other)),
+        })
+    }
+
+    /// Switches to the sRGB internal format for sRGB data. GL 3.3 only has
+    /// 8 bit RGB and RGBA sRGB formats, every other format is left alone.
+    pub fn in_color_space(self, color_space: ColorSpace) -> Self {
+        let internal_format = match (color_space, self.internal_format) {
+            (ColorSpace::Srgb, GL_RGB8) => GL_SRGB8,
+            (ColorSpace::Srgb, GL_RGBA8) => GL_SRGB8_ALPHA8,
+            (_, internal_format) => internal_format,
+        };
+        Self { internal_format, ..self }
+    }
+}
+
+/// Whether texture data is gamma encoded color or plain linear values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorSpace {
+    /// Normal maps, roughness, height maps, anything that isn't a color.
+    #[default]
+    Linear,
+    /// Colors as they come out of an image editor or camera, like albedo
+    /// maps. Sampling decodes them to linear.
+    Srgb,
+}
+
+/// The swizzle that makes a luma texture sample as gray, `(l, l, l, a)`,
+/// instead of `(l, a, 0, 1)`. `None` for color images.
+pub fn gray_swizzle(color: ColorType) -> Option<[GLenum; 4]> {
+    match color {
+        ColorType::L8 | ColorType::L16 => Some([GL_RED, GL_RED, GL_RED, GL_ONE]),
+        ColorType::La8 | ColorType::La16 => Some([GL_RED, GL_RED, GL_RED, GL_GREEN]),
+        _ => None,
+    }
+}
+
+/// Sets `GL_TEXTURE_SWIZZLE_RGBA` on the texture bound to `target`.
+pub(crate) fn set_swizzle(target: GLenum, swizzle: [GLenum; 4]) {
+    let swizzle = swizzle.map(|c| c.0 as i32);
+    unsafe { glTexParameteriv(target, GL_TEXTURE_SWIZZLE_RGBA, swizzle.as_ptr()) };
+}
+
+/// Sets `GL_UNPACK_ALIGNMENT` to the biggest alignment rows of `row_bytes`
+/// bytes have. Images keep rows tightly packed, so an RGB8 image with an odd
+/// width would come out sheared with GL's default of 4.
+pub(crate) fn set_unpack_alignment(row_bytes: usize) {
+    unsafe { glPixelStorei(GL_UNPACK_ALIGNMENT, row_alignment(row_bytes)) };
+}
+
+/// [`set_unpack_alignment`] for reading pixels back, `GL_PACK_ALIGNMENT`.
+pub(crate) fn set_pack_alignment(row_bytes: usize) {
+    unsafe { glPixelStorei(GL_PACK_ALIGNMENT, row_alignment(row_bytes)) };
+}
+
+fn row_alignment(row_bytes: usize) -> i32 {
+    [8, 4, 2, 1].into_iter().find(|a| row_bytes.is_multiple_of(*a)).unwrap_or(1) as i32
+}
@@ -0,0 +1,804 @@
+//! A small GLSL frontend that runs without a GL context.
+//!
+//! It only understands the global scope: the `#version`, `in`/`out`
+//! variables, uniforms, uniform and interface blocks, structs and functions.
+//! Function bodies are checked for balanced brackets and scanned for the
+//! names they use, but not type checked. Macros aren't expanded, so run
+//! sources through the [preprocessor](crate::preprocess) first for includes.
+//!
+//! Lines ending in `\` carry on to the next one. `#if`, `#ifdef` and
+//! friends only keep the branch that's on, going by the `#define`s seen so
+//! far. Conditions it can't work out, like `#if LIGHTS > 2`, keep their first
+//! branch.
+//!
+//! Names are only collected, not resolved, so a local variable or parameter
+//! named like a uniform still counts as a use of the uniform.
+
+use std::borrow::Cow;
+use std::collections::HashSet;
+
+use super::diagnostic::{Diagnostic, Severity};
+use super::shader::ShaderType;
+
+/// Where a global variable or block lives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Storage {
+    In,
+    Out,
+    Uniform,
+    Const,
+    Global,
+}
+
+/// A global variable, or a member of a block or struct.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variable {
+    pub name: String,
+    pub ty: String,
+    /// The text between the `[]` for arrays, empty for unsized arrays.
+    pub array: Option<String>,
+    /// From `layout(location = N)`.
+    pub location: Option<u32>,
+    pub storage: Storage,
+    /// The GLSL source string number and line it was declared on.
+    pub file: u32,
+    pub line: u32,
+}
+
+/// A uniform block, or an `in`/`out` interface block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub name: String,
+    pub instance: Option<String>,
+    pub members: Vec<Variable>,
+    pub storage: Storage,
+    pub file: u32,
+    pub line: u32,
+}
+
+/// Everything declared at global scope in one shader.
+#[derive(Debug, Clone, Default)]
+pub struct ShaderInterface {
+    pub version: Option<String>,
+    pub variables: Vec<Variable>,
+    pub blocks: Vec<Block>,
+    pub structs: Vec<String>,
+    pub functions: Vec<String>,
+    /// Every identifier that shows up in a function body or initializer.
+    pub used: HashSet<String>,
+}
+
+impl ShaderInterface {
+    /// Global variables with the given storage.
+    pub fn with_storage(&self, storage: Storage) -> impl Iterator<Item = &Variable> {
+        self.variables.iter().filter(move |v| v.storage == storage)
+    }
+
+    /// Checks if a global is read or written anywhere.
+    pub fn is_used(&self, name: &str) -> bool {
+        self.used.contains(name)
+    }
+
+    /// Checks if a block's contents are used, through its instance name or,
+    /// for blocks without one, through any of its members.
+    pub fn block_used(&self, block: &Block) -> bool {
+        match &block.instance {
+            Some(instance) => self.is_used(instance),
+            None => block.members.iter().any(|m| self.is_used(&m.name)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    Ident(String),
+    Number(String),
+    Punct(char),
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    tok: Tok,
+    file: u32,
+    line: u32,
+}
+
+/// Parses the global declarations out of a shader source string for `stage`.
+///
+/// The error is a [`Diagnostic`] pointing at the offending line. Use the
+/// `file` of the diagnostic, and of every declaration, to map back through
+/// `#line` directives.
+pub fn parse(source: &str, stage: ShaderType) -> Result<ShaderInterface, Diagnostic> {
+    let mut out = ShaderInterface::default();
+    let tokens = lex(source, &mut out.version)?;
+    let mut parser = Parser { tokens, pos: 0, stage, out };
+    parser.translation_unit()?;
+    Ok(parser.out)
+}
+
+fn error(file: u32, line: u32, message: impl Into<String>) -> Diagnostic {
+    Diagnostic {
+        file: Some(file),
+        path: None,
+        line: Some(line),
+        column: None,
+        severity: Severity::Error,
+        message: message.into(),
+        excerpt: None,
+    }
+}
+
+/// One `#if`/`#ifdef`/`#ifndef` that hasn't reached its `#endif` yet.
+struct Conditional {
+    /// Whether the code around it is on.
+    outer: bool,
+    /// Whether a branch before this one was on.
+    taken: bool,
+    /// Whether the current branch is on.
+    on: bool,
+    file: u32,
+    line: u32,
+}
+
+/// Works out an `#if` condition, `None` when it's more than a number or a
+/// `defined` test.
+fn condition(expr: &str, defines: &HashSet<String>) -> Option<bool> {
+    let expr = expr.trim();
+    if let Some(rest) = expr.strip_prefix('!') {
+        return condition(rest, defines).map(|on| !on);
+    }
+    if let Some(rest) = expr.strip_prefix("defined") {
+        let name = rest.trim().trim_start_matches('(').trim_end_matches(')').trim();
+        return (!name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+            .then(|| defines.contains(name));
+    }
+    expr.parse::<i64>().ok().map(|n| n != 0)
+}
+
+fn lex(source: &str, version: &mut Option<String>) -> Result<Vec<Token>, Diagnostic> {
+    let mut tokens = Vec::new();
+    let mut file = 0;
+    let mut line = 1;
+    let mut in_comment = false;
+    let mut defines = HashSet::new();
+    let mut conditionals: Vec<Conditional> = Vec::new();
+    let mut lines = source.lines();
+    while let Some(first) = lines.next() {
+        let this_line = line;
+        line += 1;
+        let mut text = Cow::Borrowed(first);
+        while text.ends_with('\\') {
+            let Some(next) = lines.next() else { break };
+            line += 1;
+            let mut joined = text.into_owned();
+            joined.pop();
+            joined.push_str(next);
+            text = Cow::Owned(joined);
+        }
+        let text = &*text;
+        let mut chars = text.char_indices().peekable();
+        let active = conditionals.last().is_none_or(|c| c.outer && c.on);
+
+        if !in_comment {
+            let trimmed = text.trim_start();
+            if let Some(directive) = trimmed.strip_prefix('#') {
+                let directive = directive.trim_start();
+                let (name, rest) = directive.split_once(char::is_whitespace).unwrap_or((directive, ""));
+                let mut words = rest.split_whitespace();
+                let unmatched = || error(file, this_line, format!("`#{}` without an `#if`", name));
+                match name {
+                    "version" if active => *version = Some(words.collect::<Vec<_>>().join(" ")),
+                    "line" if active => {
+                        if let Some(n) = words.next().and_then(|w| w.parse().ok()) {
+                            line = n;
+                        }
+                        if let Some(f) = words.next().and_then(|w| w.parse().ok()) {
+                            file = f;
+                        }
+                    }
+                    "define" if active => {
+                        if let Some(macro_name) = words.next() {
+                            defines.insert(macro_name.split('(').next().unwrap_or(macro_name).to_string());
+                        }
+                    }
+                    "undef" if active => {
+                        if let Some(macro_name) = words.next() {
+                            defines.remove(macro_name);
+                        }
+                    }
+                    "if" | "ifdef" | "ifndef" => {
+                        let on = match name {
+                            "ifdef" => defines.contains(rest.trim()),
+                            "ifndef" => !defines.contains(rest.trim()),
+                            _ => condition(rest, &defines).unwrap_or(true),
+                        };
+                        conditionals.push(Conditional { outer: active, taken: on, on, file, line: this_line });
+                    }
+                    "elif" => {
+                        let c = conditionals.last_mut().ok_or_else(unmatched)?;
+                        c.on = !c.taken && condition(rest, &defines).unwrap_or(true);
+                        c.taken |= c.on;
+                    }
+                    "else" => {
+                        let c = conditionals.last_mut().ok_or_else(unmatched)?;
+                        c.on = !c.taken;
+                        c.taken = true;
+                    }
+                    "endif" => {
+                        conditionals.pop().ok_or_else(unmatched)?;
+                    }
+                    _ => (),
+                }
+                continue;
+            }
+        }
+        if !active {
+            continue;
+        }
+
+        while let Some((i, c)) = chars.next() {
+            if in_comment {
+                if c == '*' && chars.peek().is_some_and(|&(_, n)| n == '/') {
+                    chars.next();
+                    in_comment = false;
+                }
+                continue;
+            }
+            match c {
+                '/' if chars.peek().is_some_and(|&(_, n)| n == '/') => break,
+                '/' if chars.peek().is_some_and(|&(_, n)| n == '*') => {
+                    chars.next();
+                    in_comment = true;
+                }
+                c if c.is_whitespace() => (),
+                c if c.is_ascii_alphabetic() || c == '_' => {
+                    let mut end = i + c.len_utf8();
+                    while let Some(&(j, n)) = chars.peek() {
+                        if n.is_ascii_alphanumeric() || n == '_' {
+                            end = j + n.len_utf8();
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                    tokens.push(Token { tok: Tok::Ident(text[i..end].to_string()), file, line: this_line });
+                }
+                c if c.is_ascii_digit() || (c == '.' && chars.peek().is_some_and(|&(_, n)| n.is_ascii_digit())) => {
+                    let mut end = i + 1;
+                    while let Some(&(j, n)) = chars.peek() {
+                        // good enough for 1.0e-5, 0x1F and 2u
+                        let exponent_sign = (n == '-' || n == '+') && text[..j].ends_with(['e', 'E']) && !text[i..j].starts_with("0x");
+                        if n.is_ascii_alphanumeric() || n == '.' || exponent_sign {
+                            end = j + 1;
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                    tokens.push(Token { tok: Tok::Number(text[i..end].to_string()), file, line: this_line });
+                }
+                c if c.is_ascii_punctuation() => tokens.push(Token { tok: Tok::Punct(c), file, line: this_line }),
+                c => return Err(error(file, this_line, format!("unexpected character `{}`", c))),
+            }
+        }
+    }
+    if in_comment {
+        return Err(error(file, line.saturating_sub(1), "unterminated block comment"));
+    }
+    if let Some(c) = conditionals.last() {
+        return Err(error(c.file, c.line, "`#if` without an `#endif`"));
+    }
+    Ok(tokens)
+}
+
+const QUALIFIERS: &[&str] = &[
+    "in", "out", "inout", "uniform", "const", "attribute", "varying", "flat", "smooth",
+    "noperspective", "centroid", "invariant", "sample", "patch", "highp", "mediump", "lowp",
+];
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    stage: ShaderType,
+    out: ShaderInterface,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Tok> {
+        self.tokens.get(self.pos).map(|t| &t.tok)
+    }
+
+    /// File and line of the current token, or of the last one at the end.
+    fn here(&self) -> (u32, u32) {
+        self.tokens
+            .get(self.pos)
+            .or_else(|| self.tokens.last())
+            .map(|t| (t.file, t.line))
+            .unwrap_or((0, 1))
+    }
+
+    fn error(&self, message: impl Into<String>) -> Diagnostic {
+        let (file, line) = self.here();
+        error(file, line, message)
+    }
+
+    fn is_punct(&self, c: char) -> bool {
+        self.peek() == Some(&Tok::Punct(c))
+    }
+
+    fn eat_punct(&mut self, c: char) -> bool {
+        if self.is_punct(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_punct(&mut self, c: char) -> Result<(), Diagnostic> {
+        if self.eat_punct(c) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected `{}`, found {}", c, self.describe())))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, Diagnostic> {
+        match self.peek() {
+            Some(Tok::Ident(name)) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => Err(self.error(format!("expected a name, found {}", self.describe()))),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self.peek() {
+            Some(Tok::Ident(s)) | Some(Tok::Number(s)) => format!("`{}`", s),
+            Some(Tok::Punct(c)) => format!("`{}`", c),
+            None => "the end of the file".to_string(),
+        }
+    }
+
+    /// Skips from an opening bracket to its partner, remembering every name
+    /// inside as used.
+    fn skip_group(&mut self, open: char, close: char) -> Result<(), Diagnostic> {
+        let (file, line) = self.here();
+        self.expect_punct(open)?;
+        let mut depth = 1;
+        while depth > 0 {
+            match self.peek() {
+                None => return Err(error(file, line, format!("`{}` is never closed", open))),
+                Some(Tok::Punct(c)) if *c == open => depth += 1,
+                Some(Tok::Punct(c)) if *c == close => depth -= 1,
+                Some(Tok::Punct(c @ (')' | ']' | '}'))) => {
+                    return Err(self.error(format!("mismatched `{}` inside `{}`", c, open)));
+                }
+                Some(Tok::Punct('(')) => {
+                    self.skip_group('(', ')')?;
+                    continue;
+                }
+                Some(Tok::Punct('[')) => {
+                    self.skip_group('[', ']')?;
+                    continue;
+                }
+                Some(Tok::Punct('{')) => {
+                    self.skip_group('{', '}')?;
+                    continue;
+                }
+                Some(Tok::Ident(name)) => {
+                    self.out.used.insert(name.clone());
+                }
+                _ => (),
+            }
+            self.pos += 1;
+        }
+        Ok(())
+    }
+
+    /// Skips an initializer up to the `,` or `;` that ends it.
+    fn skip_initializer(&mut self) -> Result<(), Diagnostic> {
+        loop {
+            match self.peek() {
+                None => return Err(self.error("expected `;` after the initializer")),
+                Some(Tok::Punct(',' | ';')) => return Ok(()),
+                Some(Tok::Punct('(')) => self.skip_group('(', ')')?,
+                Some(Tok::Punct('[')) => self.skip_group('[', ']')?,
+                Some(Tok::Punct('{')) => self.skip_group('{', '}')?,
+                Some(Tok::Ident(name)) => {
+                    self.out.used.insert(name.clone());
+                    self.pos += 1;
+                }
+                _ => self.pos += 1,
+            }
+        }
+    }
+
+    /// `[N]` after a type or a name.
+    fn array_suffix(&mut self) -> Result<Option<String>, Diagnostic> {
+        if !self.is_punct('[') {
+            return Ok(None);
+        }
+        let start = self.pos + 1;
+        self.skip_group('[', ']')?;
+        let size: Vec<String> = self.tokens[start..self.pos - 1]
+            .iter()
+            .map(|t| match &t.tok {
+                Tok::Ident(s) | Tok::Number(s) => s.clone(),
+                Tok::Punct(c) => c.to_string(),
+            })
+            .collect();
+        Ok(Some(size.join("")))
+    }
+
+    /// `layout(location = 2, std140)`, returns the location if there is one.
+    fn layout(&mut self) -> Result<Option<u32>, Diagnostic> {
+        self.expect_punct('(')?;
+        let mut location = None;
+        loop {
+            let key = self.ident()?;
+            if self.eat_punct('=') {
+                let value = match self.peek() {
+                    Some(Tok::Number(n)) | Some(Tok::Ident(n)) => n.clone(),
+                    _ => return Err(self.error(format!("expected a value for `{}`", key))),
+                };
+                self.pos += 1;
+                if key == "location" {
+                    location = value.trim_end_matches(['u', 'U']).parse().ok();
+                }
+            }
+            if self.eat_punct(')') {
+                return Ok(location);
+            }
+            self.expect_punct(',')?;
+        }
+    }
+
+    fn translation_unit(&mut self) -> Result<(), Diagnostic> {
+        while self.peek().is_some() {
+            if self.eat_punct(';') {
+                continue;
+            }
+            self.external_declaration()?;
+        }
+        Ok(())
+    }
+
+    fn external_declaration(&mut self) -> Result<(), Diagnostic> {
+        let (file, line) = self.here();
+        if self.peek() == Some(&Tok::Ident("precision".to_string())) {
+            while !self.eat_punct(';') {
+                if self.peek().is_none() {
+                    return Err(self.error("expected `;` after precision statement"));
+                }
+                self.pos += 1;
+            }
+            return Ok(());
+        }
+
+        let mut storage = Storage::Global;
+        let mut location = None;
+        loop {
+            match self.peek() {
+                Some(Tok::Ident(word)) if word == "layout" => {
+                    self.pos += 1;
+                    location = self.layout()?.or(location);
+                }
+                Some(Tok::Ident(word)) if QUALIFIERS.contains(&word.as_str()) => {
+                    storage = match word.as_str() {
+                        "in" | "attribute" => Storage::In,
+                        "out" => Storage::Out,
+                        "varying" if self.stage == ShaderType::Fragment => Storage::In,
+                        "varying" => Storage::Out,
+                        "uniform" => Storage::Uniform,
+                        "const" => Storage::Const,
+                        _ => storage,
+                    };
+                    self.pos += 1;
+                }
+                _ => break,
+            }
+        }
+
+        // a bare `layout(...) in;` like geometry shaders use
+        if self.is_punct(';') {
+            return Ok(());
+        }
+
+        let ty = self.ident()?;
+        if ty == "struct" {
+            return self.struct_declaration(storage, location, file, line);
+        }
+        if self.is_punct('{') {
+            return self.block(ty, storage, file, line);
+        }
+        let ty_array = self.array_suffix()?;
+        let name = self.ident()?;
+        if self.is_punct('(') {
+            return self.function(name);
+        }
+        self.declarators(ty, ty_array, name, storage, location)
+    }
+
+    fn struct_declaration(&mut self, storage: Storage, location: Option<u32>, file: u32, line: u32) -> Result<(), Diagnostic> {
+        let name = self.ident()?;
+        self.member_list(Storage::Global, file, line)?;
+        self.out.structs.push(name.clone());
+        if self.eat_punct(';') {
+            return Ok(());
+        }
+        let first = self.ident()?;
+        self.declarators(name, None, first, storage, location)
+    }
+
+    fn block(&mut self, name: String, storage: Storage, file: u32, line: u32) -> Result<(), Diagnostic> {
+        let members = self.member_list(storage, file, line)?;
+        let instance = match self.peek() {
+            Some(Tok::Ident(_)) => {
+                let instance = self.ident()?;
+                self.array_suffix()?;
+                Some(instance)
+            }
+            _ => None,
+        };
+        self.expect_punct(';')?;
+        self.out.blocks.push(Block { name, instance, members, storage, file, line });
+        Ok(())
+    }
+
+    /// The `{ type name; ... }` of a struct or block.
+    fn member_list(&mut self, storage: Storage, file: u32, line: u32) -> Result<Vec<Variable>, Diagnostic> {
+        let mut members = Vec::new();
+        self.expect_punct('{')?;
+        while !self.eat_punct('}') {
+            if self.peek().is_none() {
+                return Err(error(file, line, "`{` is never closed"));
+            }
+            let mut location = None;
+            while let Some(Tok::Ident(word)) = self.peek() {
+                if word == "layout" {
+                    self.pos += 1;
+                    location = self.layout()?;
+                } else if QUALIFIERS.contains(&word.as_str()) {
+                    self.pos += 1;
+                } else {
+                    break;
+                }
+            }
+            let (member_file, member_line) = self.here();
+            let ty = self.ident()?;
+            let ty_array = self.array_suffix()?;
+            loop {
+                let name = self.ident()?;
+                let array = self.array_suffix()?.or(ty_array.clone());
+                members.push(Variable {
+                    name,
+                    ty: ty.clone(),
+                    array,
+                    location,
+                    storage,
+                    file: member_file,
+                    line: member_line,
+                });
+                if !self.eat_punct(',') {
+                    break;
+                }
+            }
+            self.expect_punct(';')?;
+        }
+        Ok(members)
+    }
+
+    fn declarators(&mut self, ty: String, ty_array: Option<String>, first: String, storage: Storage, location: Option<u32>) -> Result<(), Diagnostic> {
+        let mut name = first;
+        loop {
+            let (file, line) = self.here();
+            let array = self.array_suffix()?.or(ty_array.clone());
+            if self.eat_punct('=') {
+                self.skip_initializer()?;
+            }
+            self.out.variables.push(Variable {
+                name,
+                ty: ty.clone(),
+                array,
+                location,
+                storage,
+                file,
+                line,
+            });
+            if self.eat_punct(';') {
+                return Ok(());
+            }
+            self.expect_punct(',')?;
+            name = self.ident()?;
+        }
+    }
+
+    fn function(&mut self, name: String) -> Result<(), Diagnostic> {
+        // parameter names aren't uses, even when a uniform has the same name
+        let used = std::mem::take(&mut self.out.used);
+        let params = self.skip_group('(', ')');
+        self.out.used = used;
+        params?;
+        if !self.eat_punct(';') {
+            if !self.is_punct('{') {
+                return Err(self.error(format!("expected `{{` or `;` after `{}(...)`, found {}", name, self.describe())));
+            }
+            self.skip_group('{', '}')?;
+        }
+        self.out.functions.push(name);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(vars: impl Iterator<Item = impl std::ops::Deref<Target = Variable>>) -> Vec<String> {
+        vars.map(|v| v.name.clone()).collect()
+    }
+
+    #[test]
+    fn globals_and_locations() {
+        let iface = parse(
+            "#version 330 core\n\
+             layout (location = 0) in vec3 aPos;\n\
+             layout (location = 4) in mat4 aModel;\n\
+             out vec2 uv, other[2];\n\
+             uniform sampler2D brick;\n\
+             const float scale = 2.0;\n\
+             void main() { gl_Position = aModel * vec4(aPos * scale, 1.0); }\n",
+            ShaderType::Vertex,
+        )
+        .unwrap();
+        assert_eq!(iface.version.as_deref(), Some("330 core"));
+        let inputs: Vec<_> = iface.with_storage(Storage::In).map(|v| (v.name.as_str(), v.ty.as_str(), v.location)).collect();
+        assert_eq!(inputs, [("aPos", "vec3", Some(0)), ("aModel", "mat4", Some(4))]);
+        let outputs: Vec<_> = iface.with_storage(Storage::Out).map(|v| (v.name.as_str(), v.array.as_deref())).collect();
+        assert_eq!(outputs, [("uv", None), ("other", Some("2"))]);
+        assert_eq!(names(iface.with_storage(Storage::Uniform)), ["brick"]);
+        assert_eq!(iface.functions, ["main"]);
+        assert!(iface.is_used("aModel") && iface.is_used("scale"));
+        assert!(!iface.is_used("brick"));
+    }
+
+    #[test]
+    fn uniform_blocks() {
+        let iface = parse(
+            "layout (std140) uniform Camera {\n\
+                 mat4 view;\n\
+                 mat4 projection;\n\
+             };\n\
+             uniform Lights { vec4 colors[4]; int count; } lights;\n\
+             uniform Unused { float x; };\n\
+             void main() { gl_Position = projection * view * lights.colors[0]; }\n",
+            ShaderType::Vertex,
+        )
+        .unwrap();
+        assert_eq!(iface.blocks.len(), 3);
+        let camera = &iface.blocks[0];
+        assert_eq!((camera.name.as_str(), camera.instance.as_deref(), camera.storage), ("Camera", None, Storage::Uniform));
+        assert_eq!(names(camera.members.iter()), ["view", "projection"]);
+        assert_eq!(iface.blocks[1].instance.as_deref(), Some("lights"));
+        assert_eq!(iface.blocks[1].members[0].array.as_deref(), Some("4"));
+        assert!(iface.block_used(&iface.blocks[0]));
+        assert!(iface.block_used(&iface.blocks[1]));
+        assert!(!iface.block_used(&iface.blocks[2]));
+    }
+
+    #[test]
+    fn comments_and_strings_hide_declarations() {
+        // GLSL only has strings in directives
+        let iface = parse(
+            "// uniform float commented;\n\
+             #pragma message \"uniform float in_string;\"\n\
+             #if 0\n\
+             #error \"in vec3 in_error;\"\n\
+             #endif\n\
+             /* uniform float block_commented;\n\
+                in vec3 also; */\n\
+             uniform float kept; /* uniform float inline; */\n\
+             void main() { /* kept */ }\n",
+            ShaderType::Fragment,
+        )
+        .unwrap();
+        assert_eq!(names(iface.variables.iter()), ["kept"]);
+        assert!(!iface.is_used("kept"));
+    }
+
+    #[test]
+    fn only_the_active_branch_is_parsed() {
+        let iface = parse(
+            "#define SHADOWS\n\
+             #ifdef SHADOWS\n\
+             uniform sampler2D shadow_map;\n\
+             #else\n\
+             uniform float shadow_map;\n\
+             #endif\n\
+             #if 0\n\
+             uniform float off;\n\
+             #elif defined(SHADOWS)\n\
+             uniform float on;\n\
+             #else\n\
+             uniform float also_off;\n\
+             #endif\n\
+             #ifndef SHADOWS\n\
+             uniform float not_defined;\n\
+             #endif\n\
+             void main() {}\n",
+            ShaderType::Fragment,
+        )
+        .unwrap();
+        let uniforms: Vec<_> = iface.with_storage(Storage::Uniform).map(|v| (v.name.as_str(), v.ty.as_str())).collect();
+        assert_eq!(uniforms, [("shadow_map", "sampler2D"), ("on", "float")]);
+
+        let err = parse("#ifdef A\nvoid main() {}\n", ShaderType::Fragment).unwrap_err();
+        assert_eq!(err.line, Some(1));
+        assert!(parse("#endif\n", ShaderType::Fragment).is_err());
+    }
+
+    #[test]
+    fn line_continuations() {
+        let iface = parse(
+            "#define LONG \\\n    1\n\
+             uniform vec3 \\\n    color;\n\
+             in vec2 uv;\n\
+             void main() {}\n",
+            ShaderType::Fragment,
+        )
+        .unwrap();
+        assert_eq!(names(iface.with_storage(Storage::Uniform)), ["color"]);
+        let uv = iface.with_storage(Storage::In).next().unwrap();
+        assert_eq!(uv.line, 5);
+    }
+
+    #[test]
+    fn parameters_arent_uses() {
+        let iface = parse(
+            "uniform vec3 color;\n\
+             uniform vec3 tint;\n\
+             vec3 shade(vec3 color) { return color; }\n\
+             void main() { shade(tint); }\n",
+            ShaderType::Fragment,
+        )
+        .unwrap();
+        // `color` in the body is the parameter, but names aren't resolved
+        assert!(iface.is_used("tint"));
+        assert!(iface.is_used("color"));
+        let iface = parse("uniform vec3 color;\nvec3 shade(vec3 color);\nvoid main() {}\n", ShaderType::Fragment).unwrap();
+        assert!(!iface.is_used("color"));
+    }
+
+    #[test]
+    fn errors_follow_line_directives() {
+        let err = parse(
+            "#version 330 core\n\
+             #line 1 1\n\
+             uniform float a;\n\
+             uniform float b\n\
+             #line 3 0\n\
+             void main() {}\n",
+            ShaderType::Fragment,
+        )
+        .unwrap_err();
+        // `void` on line 3 of file 0 is where the `;` was expected
+        assert_eq!((err.file, err.line), (Some(0), Some(3)));
+        assert!(err.message.contains("expected `,`"), "{}", err.message);
+
+        let iface = parse("#line 10 2\nin vec3 normal;\nvoid main() {}\n", ShaderType::Fragment).unwrap();
+        let normal = iface.with_storage(Storage::In).next().unwrap();
+        assert_eq!((normal.file, normal.line), (2, 10));
+    }
+
+    #[test]
+    fn unbalanced_brackets_are_errors() {
+        let err = parse("void main() {\n    if (x) {\n}\n", ShaderType::Fragment).unwrap_err();
+        assert_eq!(err.line, Some(1));
+        assert!(err.message.contains("never closed"), "{}", err.message);
+        let err = parse("void main() { f(]; }\n", ShaderType::Fragment).unwrap_err();
+        assert!(err.message.contains("mismatched"), "{}", err.message);
+    }
+}
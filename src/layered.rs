@@ -0,0 +1,303 @@
+//! Textures with more than one image: `GL_TEXTURE_2D_ARRAY` and
+//! `GL_TEXTURE_3D`.
+
+use std::path::Path;
+
+use gl33::{*, global_loader::*};
+use image::{DynamicImage, ImageReader};
+
+use super::unpack_enum;
+use super::Error;
+use super::format::{self, PixelFormat};
+use super::sampler::TextureParams;
+use super::texture::{mip_chain, LoadOptions, Mipmaps};
+
+/// What the array and 3D textures have in common, they only differ in
+/// whether the mip levels shrink in depth.
+struct Layered {
+    id: u32,
+    target: GLenum,
+    width: u32,
+    height: u32,
+    depth: u32,
+    format: PixelFormat,
+}
+
+impl Layered {
+    fn new(target: GLenum, width: u32, height: u32, depth: u32, format: PixelFormat) -> Result<Self, Error> {
+        let mut id = 0;
+        unsafe { glGenTextures(1, &mut id) };
+        if id == 0 {
+            return Err(Error::Alloc("texture"));
+        }
+        let layered = Self { id, target, width, height, depth, format };
+        layered.allocate_level(0);
+        unsafe {
+            glTexParameteri(target, GL_TEXTURE_BASE_LEVEL, 0);
+            glTexParameteri(target, GL_TEXTURE_MAX_LEVEL, 0);
+        }
+        Ok(layered)
+    }
+
+    /// Size of a mip level, array layers don't shrink but 3D slices do.
+    fn level_size(&self, level: u32) -> (u32, u32, u32) {
+        let depth = if self.target == GL_TEXTURE_3D { (self.depth >> level).max(1) } else { self.depth };
+        ((self.width >> level).max(1), (self.height >> level).max(1), depth)
+    }
+
+    fn allocate_level(&self, level: u32) {
+        let (width, height, depth) = self.level_size(level);
+        unsafe {
+            glBindTexture(self.target, self.id);
+            glTexImage3D(
+                self.target,
+                level as i32,
+                unpack_enum(self.format.internal_format),
+                width as i32,
+                height as i32,
+                depth as i32,
+                0,
+                self.format.format,
+                self.format.ty,
+                std::ptr::null(),
+            );
+        }
+    }
+
+    fn bind(&self, tex_unit: GLenum) {
+        unsafe {
+            glActiveTexture(tex_unit);
+            glBindTexture(self.target, self.id);
+        }
+    }
+
+    fn set_params(&self, params: &TextureParams) {
+        unsafe { glBindTexture(self.target, self.id) };
+        params.apply(self.target);
+    }
+
+    /// Replaces one layer or slice of a mip level with `img`. GL converts the
+    /// image's channels to the texture's format, only the size has to match.
+    fn upload_layer(&self, level: u32, layer: u32, img: &DynamicImage) -> Result<(), Error> {
+        let (width, height, depth) = self.level_size(level);
+        if img.width() != width || img.height() != height {
+            return Err(Error::TextureSize(format!(
+                "level {} is {}x{}, the image is {}x{}",
+                level,
+                width,
+                height,
+                img.width(),
+                img.height()
+            )));
+        }
+        if layer >= depth {
+            return Err(Error::TextureSize(format!("level {} only has {} layers, can't set layer {}", level, depth, layer)));
+        }
+        let src = PixelFormat::of(img.color())?;
+        format::set_unpack_alignment(width as usize * src.bytes_per_pixel);
+        unsafe {
+            glBindTexture(self.target, self.id);
+            glTexSubImage3D(
+                self.target,
+                level as i32,
+                0,
+                0,
+                layer as i32,
+                width as i32,
+                height as i32,
+                1,
+                src.format,
+                src.ty,
+                img.as_bytes().as_ptr().cast(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Replaces all of level 0 with raw data in the texture's own format.
+    fn upload_raw(&self, data: &[u8]) -> Result<(), Error> {
+        let expected = self.width as usize * self.height as usize * self.depth as usize * self.format.bytes_per_pixel;
+        if data.len() != expected {
+            return Err(Error::TextureSize(format!("expected {} bytes of data, got {}", expected, data.len())));
+        }
+        format::set_unpack_alignment(self.width as usize * self.format.bytes_per_pixel);
+        unsafe {
+            glBindTexture(self.target, self.id);
+            glTexSubImage3D(
+                self.target,
+                0,
+                0,
+                0,
+                0,
+                self.width as i32,
+                self.height as i32,
+                self.depth as i32,
+                self.format.format,
+                self.format.ty,
+                data.as_ptr().cast(),
+            );
+        }
+        Ok(())
+    }
+
+    fn generate_mipmaps(&self) {
+        unsafe {
+            glBindTexture(self.target, self.id);
+            glTexParameteri(self.target, GL_TEXTURE_MAX_LEVEL, 1000);
+            glGenerateMipmap(self.target);
+        }
+    }
+
+    /// Fills the texture from images, one per layer, as `options` says.
+    fn load_images(&self, images: &[DynamicImage], options: &LoadOptions) -> Result<(), Error> {
+        match options.mipmaps {
+            // 3D mip levels mix neighbouring slices, which the image crate
+            // can't do, so the driver makes those.
+            Mipmaps::Cpu(filter) if self.target != GL_TEXTURE_3D => {
+                let chains: Vec<Vec<DynamicImage>> = images.iter().map(|img| mip_chain(img, filter)).collect();
+                let levels = chains.iter().map(Vec::len).min().unwrap_or(1);
+                for level in 0..levels {
+                    if level > 0 {
+                        self.allocate_level(level as u32);
+                    }
+                    for (layer, chain) in chains.iter().enumerate() {
+                        self.upload_layer(level as u32, layer as u32, &chain[level])?;
+                    }
+                }
+                unsafe { glTexParameteri(self.target, GL_TEXTURE_MAX_LEVEL, levels as i32 - 1) };
+            }
+            Mipmaps::None => {
+                for (layer, img) in images.iter().enumerate() {
+                    self.upload_layer(0, layer as u32, img)?;
+                }
+            }
+            Mipmaps::Generate | Mipmaps::Cpu(_) => {
+                for (layer, img) in images.iter().enumerate() {
+                    self.upload_layer(0, layer as u32, img)?;
+                }
+                self.generate_mipmaps();
+            }
+        }
+        if let Some(swizzle) = images.first().and_then(|img| format::gray_swizzle(img.color())).filter(|_| options.gray_swizzle) {
+            format::set_swizzle(self.target, swizzle);
+        }
+        Ok(())
+    }
+
+    /// Makes a texture with one layer per image file. The format comes from
+    /// the first image and every image has to be the same size.
+    fn from_files(target: GLenum, paths: &[impl AsRef<Path>], options: &LoadOptions) -> Result<Self, Error> {
+        let mut images = Vec::with_capacity(paths.len());
+        for path in paths {
+            images.push(ImageReader::open(path)?.decode()?.flipv());
+        }
+        let first = images.first().ok_or_else(|| Error::TextureSize("no images to make layers from".to_string()))?;
+        let format = PixelFormat::of(first.color())?.in_color_space(options.color_space);
+        let layered = Self::new(target, first.width(), first.height(), images.len() as u32, format)?;
+        layered.load_images(&images, options)?;
+        Ok(layered)
+    }
+}
+
+impl Drop for Layered {
+    fn drop(&mut self) {
+        unsafe { glDeleteTextures(1, &self.id) };
+    }
+}
+
+/// A `GL_TEXTURE_2D_ARRAY`, a stack of same sized 2D images sampled with a
+/// `sampler2DArray` and a layer index. Good for terrain splat maps.
+pub struct Texture2DArray(Layered);
+impl Texture2DArray {
+    /// Allocates an empty array, fill it in with
+    /// [`upload_layer`](Self::upload_layer). Use
+    /// [`PixelFormat::in_color_space`] for sRGB layers.
+    pub fn new(width: u32, height: u32, layers: u32, format: PixelFormat) -> Result<Self, Error> {
+        Layered::new(GL_TEXTURE_2D_ARRAY, width, height, layers, format).map(Self)
+    }
+
+    /// Makes an array with one layer per image file, in order.
+    pub fn from_files(paths: &[impl AsRef<Path>], options: &LoadOptions) -> Result<Self, Error> {
+        Layered::from_files(GL_TEXTURE_2D_ARRAY, paths, options).map(Self)
+    }
+
+    pub fn bind(&self, tex_unit: GLenum) {
+        self.0.bind(tex_unit);
+    }
+
+    pub fn set_params(&self, params: &TextureParams) {
+        self.0.set_params(params);
+    }
+
+    /// Replaces one layer of level 0 with an image of the same size, flip it
+    /// first if it came straight from a file.
+    pub fn upload_layer(&self, layer: u32, img: &DynamicImage) -> Result<(), Error> {
+        self.0.upload_layer(0, layer, img)
+    }
+
+    /// Rebuilds the mip levels from level 0, eg after uploading layers.
+    pub fn generate_mipmaps(&self) {
+        self.0.generate_mipmaps();
+    }
+
+    pub fn id(&self) -> u32 {
+        self.0.id
+    }
+
+    pub fn layers(&self) -> u32 {
+        self.0.depth
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.0.width, self.0.height)
+    }
+}
+
+/// A `GL_TEXTURE_3D`, a volume sampled with a `sampler3D` and a 3D
+/// coordinate, eg a color grading lookup table.
+pub struct Texture3D(Layered);
+impl Texture3D {
+    /// Allocates an empty volume, fill it in with
+    /// [`upload_slice`](Self::upload_slice) or [`upload_raw`](Self::upload_raw).
+    pub fn new(width: u32, height: u32, depth: u32, format: PixelFormat) -> Result<Self, Error> {
+        Layered::new(GL_TEXTURE_3D, width, height, depth, format).map(Self)
+    }
+
+    /// Makes a volume with one slice per image file, in order. CPU mipmaps
+    /// aren't possible for volumes, [`Mipmaps::Cpu`] generates them on the
+    /// GPU instead.
+    pub fn from_files(paths: &[impl AsRef<Path>], options: &LoadOptions) -> Result<Self, Error> {
+        Layered::from_files(GL_TEXTURE_3D, paths, options).map(Self)
+    }
+
+    pub fn bind(&self, tex_unit: GLenum) {
+        self.0.bind(tex_unit);
+    }
+
+    pub fn set_params(&self, params: &TextureParams) {
+        self.0.set_params(params);
+    }
+
+    /// Replaces one depth slice of level 0 with an image of the same size.
+    pub fn upload_slice(&self, z: u32, img: &DynamicImage) -> Result<(), Error> {
+        self.0.upload_layer(0, z, img)
+    }
+
+    /// Replaces the whole volume with tightly packed data in the texture's
+    /// format, x fastest, then y, then z.
+    pub fn upload_raw(&self, data: &[u8]) -> Result<(), Error> {
+        self.0.upload_raw(data)
+    }
+
+    pub fn generate_mipmaps(&self) {
+        self.0.generate_mipmaps();
+    }
+
+    pub fn id(&self) -> u32 {
+        self.0.id
+    }
+
+    pub fn size(&self) -> (u32, u32, u32) {
+        (self.0.width, self.0.height, self.0.depth)
+    }
+}
@@ -1,21 +1,50 @@
 #![allow(non_snake_case)]
 #![allow(temporary_cstring_as_ptr)]
 
+pub mod atlas;
+pub mod buffer;
+pub mod compressed;
+pub mod cubemap;
+pub mod diagnostic;
+pub mod error;
+pub mod format;
+pub mod glsl;
+pub mod layered;
+pub mod mesh;
+pub mod preprocess;
+pub mod primitives;
+#[cfg(feature = "window")]
+pub mod program_cache;
+pub mod reload;
+pub mod sampler;
 pub mod shader;
+pub mod skybox;
+pub mod std140;
 pub mod texture;
+pub mod texture_unit;
+pub mod uniform;
+pub mod uniform_buffer;
+pub mod vertex;
+
+pub use error::Error;
+pub use bytemuck;
 
 use gl33::{*, global_loader::*};
 
+#[cfg(feature = "window")]
 use beryllium::Sdl;
+#[cfg(feature = "window")]
 use beryllium::init::InitFlags;
+#[cfg(feature = "window")]
 use beryllium::video::{CreateWinArgs, GlContextFlags, GlProfile, GlWindow};
 
 use std::fs;
 
-pub fn load_shader_file(file: &str) -> String {
-    fs::read_to_string(file).expect("Bad shader file")
+pub fn load_shader_file(file: &str) -> Result<String, Error> {
+    Ok(fs::read_to_string(file)?)
 }
 
+#[cfg(feature = "window")]
 pub fn load_gl(win: &GlWindow){
     unsafe {
         load_global_gl(&|context| win.get_proc_address(context) as *const _);
@@ -24,21 +53,41 @@ pub fn load_gl(win: &GlWindow){
 }
 
 
-pub fn create_context() -> Sdl {
+/// Settings for the GL context and the windows made with it.
+#[cfg(feature = "window")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ContextOptions {
+    /// Ask for an sRGB capable default framebuffer. Turn the conversion on
+    /// with [`set_framebuffer_srgb`] once GL is loaded.
+    pub srgb: bool,
+}
+
+#[cfg(feature = "window")]
+pub fn create_context() -> Result<Sdl, Error> {
+    create_context_with(&ContextOptions::default())
+}
+
+/// Like [`create_context`], the options apply to every window made after.
+#[cfg(feature = "window")]
+pub fn create_context_with(options: &ContextOptions) -> Result<Sdl, Error> {
     let sdl = Sdl::init(InitFlags::EVERYTHING);
-    sdl.set_gl_context_major_version(3).unwrap();
-    sdl.set_gl_context_minor_version(3).unwrap();
-    sdl.set_gl_profile(GlProfile::Core).unwrap();
+    sdl.set_gl_context_major_version(3)?;
+    sdl.set_gl_context_minor_version(3)?;
+    sdl.set_gl_profile(GlProfile::Core)?;
     let mut flags = GlContextFlags::default();
   
     if cfg!(debug_assertions) {
       flags |= GlContextFlags::DEBUG;
     }
-    sdl.set_gl_context_flags(flags).unwrap();    
-    sdl
+    sdl.set_gl_context_flags(flags)?;    
+    if options.srgb {
+      sdl.set_gl_framebuffer_srgb_capable(true)?;
+    }
+    Ok(sdl)
 }
 
-pub fn create_window(sdl: &Sdl, name: &str, width: i32, height: i32) -> GlWindow {
+#[cfg(feature = "window")]
+pub fn create_window(sdl: &Sdl, name: &str, width: i32, height: i32) -> Result<GlWindow, Error> {
 
     let win = sdl
     .create_gl_window(CreateWinArgs {
@@ -48,10 +97,47 @@ pub fn create_window(sdl: &Sdl, name: &str, width: i32, height: i32) -> GlWindow
       resizable: true,
       allow_high_dpi: true,
       ..Default::default()
-    })
-    .expect("couldn't make a window and context");
+    })?;
     // win.set_swap_interval(beryllium::video::GlSwapInterval::Immediate).expect("Can't set interval");
-    win
+    Ok(win)
+}
+
+/// Turns `GL_FRAMEBUFFER_SRGB` on or off. While it's on, shader outputs are
+/// treated as linear and encoded to sRGB when written to an sRGB framebuffer.
+pub fn set_framebuffer_srgb(enabled: bool) {
+    unsafe {
+        if enabled {
+            glEnable(GL_FRAMEBUFFER_SRGB);
+        } else {
+            glDisable(GL_FRAMEBUFFER_SRGB);
+        }
+    }
+}
+
+/// Checks if the window's framebuffer actually came out sRGB, drivers are
+/// allowed to ignore [`ContextOptions::srgb`].
+pub fn default_framebuffer_is_srgb() -> bool {
+    let mut encoding = 0;
+    unsafe {
+        glGetFramebufferAttachmentParameteriv(
+            GL_FRAMEBUFFER,
+            GL_BACK_LEFT,
+            GL_FRAMEBUFFER_ATTACHMENT_COLOR_ENCODING,
+            &mut encoding,
+        );
+    }
+    encoding == GL_SRGB.0 as i32
+}
+
+/// Checks the context's extension list for `name`, eg
+/// `"GL_EXT_texture_compression_s3tc"`. Call it after [`load_gl`].
+pub fn has_extension(name: &str) -> bool {
+    let mut count = 0;
+    unsafe { glGetIntegerv(GL_NUM_EXTENSIONS, &mut count) };
+    (0..count.max(0) as u32).any(|i| {
+        let ptr = unsafe { glGetStringi(GL_EXTENSIONS, i) };
+        !ptr.is_null() && unsafe { std::ffi::CStr::from_ptr(ptr.cast()) }.to_bytes() == name.as_bytes()
+    })
 }
 
 pub fn set_clear_color(r: f32, g: f32, b: f32, a: f32){
@@ -74,13 +160,13 @@ pub fn unpack_enum(gl_enum: GLenum) -> i32{
 pub struct VertexArray(pub u32);
 impl VertexArray {
     ///Create new VAO
-    pub fn new() -> Option<Self> {
+    pub fn new() -> Result<Self, Error> {
         let mut vao = 0;
         unsafe { glGenVertexArrays(1, &mut vao)};
         if vao != 0 {
-            Some(Self(vao))
+            Ok(Self(vao))
         } else {
-            None
+            Err(Error::Alloc("vertex array"))
         }
     }
 
@@ -89,6 +175,14 @@ impl VertexArray {
         glBindVertexArray(self.0);
     }
 
+    /// Binds this VAO and `buffer`, then points the attributes in `layout` at
+    /// the buffer. The VAO stays bound.
+    pub fn set_layout(&self, buffer: &BufferObject, layout: &vertex::VertexLayout) {
+        self.bind();
+        buffer.bind(BufferType::Array);
+        layout.apply();
+    }
+
     ///Clear VAO binding to GL
     pub fn clear_bind() {
         glBindVertexArray(0);
@@ -111,18 +205,20 @@ pub enum BufferType {
     Array = GL_ARRAY_BUFFER.0,
     /// Element Array Buffers hold indexes of what vertexes to use for drawing.
     ElementArray = GL_ELEMENT_ARRAY_BUFFER.0,
+    /// Uniform Buffers hold the data of uniform blocks shared between programs.
+    Uniform = GL_UNIFORM_BUFFER.0,
 }
 
 pub struct BufferObject(pub u32);
 impl BufferObject {
     ///Makes new VBO
-    pub fn new() -> Option<Self> {
+    pub fn new() -> Result<Self, Error> {
         let mut vbo = 0;
         unsafe { glGenBuffers(1, &mut vbo)};
         if vbo != 0 {
-            Some(Self(vbo))
+            Ok(Self(vbo))
         } else {
-            None
+            Err(Error::Alloc("buffer object"))
         }
     }
 
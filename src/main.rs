@@ -3,21 +3,47 @@
 #![allow(temporary_cstring_as_ptr)]
 
 
-use gl33::{*, global_loader::*};
+use gl33::global_loader::*;
 
 use beryllium::events::*;
 
 use learn_openGL as learn;
 use learn::*;
+use learn::mesh::{InstanceBuffer, Mesh};
+use learn::primitives::{self, PrimitiveVertex};
 use learn::shader::ShaderProgram;
-use learn::texture::Texture;
+use learn::reload::ReloadableProgram;
+use learn::format::ColorSpace;
+use learn::texture::{LoadOptions, Mipmaps, Texture};
+use learn::texture_unit::TextureUnits;
+use learn::sampler::{MinFilter, TextureParams, Wrap};
+use learn::cubemap::CubeMap;
+use learn::skybox::Skybox;
+use learn::uniform_buffer::UniformBuffer;
+use learn::std140;
 
 use std::collections::HashSet;
 use std::time;
 
 use nalgebra_glm as glm;
 
+std140_struct! {
+  /// Matches the `Camera` block in `shaders/vertex.vert`.
+  struct Camera {
+    view: std140::Mat4,
+    projection: std140::Mat4,
+  }
+}
+
+vertex_struct! {
+  /// Per cube data, matches `aModel` in `shaders/vertex.vert`.
+  struct Instance {
+    model: [[f32; 4]; 4],
+  }
+}
 
+/// Uniform buffer binding point the camera block lives on.
+const CAMERA_BINDING: u32 = 0;
 
 ///TODO: 
 /// hash movements
@@ -28,9 +54,9 @@ fn main() {
   let mut win_width = 1920;
   let mut win_height = 1080;
 
-  let sdl = create_context();
+  let sdl = create_context_with(&ContextOptions { srgb: true }).expect("Can't make context");
 
-  let win = create_window(&sdl, "Bev Window", win_width, win_height);
+  let win = create_window(&sdl, "Bev Window", win_width, win_height).expect("couldn't make a window and context");
 
   let cube_pos: [glm::Vec3; 10] = 
         [glm::vec3( 0.0, 0.0, 0.0),
@@ -44,110 +70,55 @@ fn main() {
         glm::vec3( 1.5, 0.2,-1.5),
         glm::vec3(-1.3, 1.0,-1.5)];
 
-  type Vertex = [f32; 5];
-  const VERTICES: [f32; 36 * 5] = [
-    -0.5,-0.5,-0.5, 0.0, 0.0,
-    0.5,-0.5,-0.5, 1.0, 0.0,
-    0.5, 0.5,-0.5, 1.0, 1.0,
-    0.5, 0.5,-0.5, 1.0, 1.0,
-    -0.5, 0.5,-0.5, 0.0,1.0,
-    -0.5,-0.5,-0.5, 0.0,0.0,
-
-    -0.5,-0.5, 0.5, 0.0,0.0,
-    0.5,-0.5, 0.5, 1.0,0.0,
-    0.5, 0.5, 0.5, 1.0,1.0,
-    0.5, 0.5, 0.5, 1.0,1.0,
-    -0.5, 0.5, 0.5, 0.0,1.0,
-    -0.5,-0.5, 0.5, 0.0,0.0,
-
-    -0.5, 0.5, 0.5, 1.0,0.0,
-    -0.5, 0.5,-0.5, 1.0,1.0,
-    -0.5,-0.5,-0.5, 0.0,1.0,
-    -0.5,-0.5,-0.5, 0.0,1.0,
-    -0.5,-0.5, 0.5, 0.0,0.0,
-    -0.5, 0.5, 0.5, 1.0,0.0,
-
-    0.5, 0.5, 0.5, 1.0,0.0,
-    0.5, 0.5,-0.5, 1.0,1.0,
-    0.5,-0.5,-0.5, 0.0,1.0,
-    0.5,-0.5,-0.5, 0.0,1.0,
-    0.5,-0.5, 0.5, 0.0,0.0,
-    0.5, 0.5, 0.5, 1.0,0.0,
-
-    -0.5,-0.5,-0.5, 0.0,1.0,
-    0.5,-0.5,-0.5, 1.0,1.0,
-    0.5,-0.5, 0.5, 1.0,0.0,
-    0.5,-0.5, 0.5, 1.0,0.0,
-    -0.5,-0.5, 0.5, 0.0,0.0,
-    -0.5,-0.5,-0.5, 0.0,1.0,
-
-    -0.5, 0.5,-0.5, 0.0,1.0,
-    0.5, 0.5,-0.5, 1.0,1.0,
-    0.5, 0.5, 0.5, 1.0,0.0,
-    0.5, 0.5, 0.5, 1.0,0.0,
-    -0.5, 0.5, 0.5, 0.0,0.0,
-    -0.5, 0.5,-0.5, 0.0, 1.0];
-
-
-  let vert_shader = load_shader_file("shaders/vertex.vert");
-  let frag_shader= load_shader_file("shaders/frag.frag");
 
   load_gl(&win);
 
-  set_clear_color(0.0, 0.5, 0.5, 1.0);
+  // shaders work in linear space, GL encodes to sRGB on the way out
+  set_framebuffer_srgb(true);
+  if !default_framebuffer_is_srgb() {
+    println!("Couldn't get an sRGB framebuffer, colors will look dark");
+  }
+  // the linear value of the old (0.0, 0.5, 0.5) teal
+  set_clear_color(0.0, 0.214, 0.214, 1.0);
 
-  let vao = VertexArray::new().expect("Can't make new VAO");
-  vao.bind();
+  let mut cube = primitives::cube(1.0).upload().expect("Can't make cube mesh");
+  let mut cube_instances = InstanceBuffer::<Instance>::new().expect("Can't make instance buffer");
+  cube.attach_instances(&cube_instances);
 
-  let vbo = BufferObject::new().expect("Can't make VBO");
-  vbo.bind(BufferType::Array);
-  buffer_data(BufferType::Array, bytemuck::cast_slice(&VERTICES), GL_STATIC_DRAW);
 
+  // mipmaps keep the far away cubes from shimmering
+  let tex_params = TextureParams::new().min_filter(MinFilter::LinearMipmapLinear);
+  let load_options = LoadOptions::new().mipmaps(Mipmaps::Generate).color_space(ColorSpace::Srgb);
 
   let tex = Texture::new().expect("Can't make Texture Object");
-  tex.bind(GL_TEXTURE0);
-  tex.setParams();
-  tex.loadTexFile("textures/brick.jpg");
+  tex.set_params(&tex_params);
+  tex.load_file("textures/brick.jpg", &load_options).expect("Can't load texture");
 
   let tex2 = Texture::new().expect("Can't make Texture Object");
-  tex2.bind(GL_TEXTURE1);
-  tex2.setParams();
-  tex2.loadTexFile("textures/face.png");
-
-
-unsafe {
-
-  glVertexAttribPointer(
-    0,
-    3,
-    GL_FLOAT,
-    GL_FALSE.0 as u8,
-    size_of::<Vertex>().try_into().unwrap(),
-    0 as *const _,
-  );
-  glEnableVertexAttribArray(0);
-  glVertexAttribPointer(
-    1,
-    2,
-    GL_FLOAT,
-    GL_FALSE.0 as u8,
-    size_of::<Vertex>().try_into().unwrap(),
-    (3 * size_of::<f32>()) as *const _,
-  );
-  glEnableVertexAttribArray(1);
-}
+  tex2.set_params(&tex_params);
+  tex2.load_file("textures/face.png", &load_options).expect("Can't load texture");
+
+  let skybox = load_skybox()
+    .map_err(|e| println!("No skybox, drawing on the clear color: {}", e))
+    .ok();
 
 
-  let shader_program = ShaderProgram::from_vert_frag(vert_shader, frag_shader).unwrap();
-  shader_program.use_program();
+  let mut shaders = ReloadableProgram::from_files("shaders/vertex.vert", "shaders/frag.frag")
+    .unwrap_or_else(|e| panic!("{}", e));
+  shaders.program().use_program();
+  setup_program(shaders.program(), &cube);
 
-  shader_program.setInt("brick", vec![0]);
-  shader_program.setInt("face", vec![1]);
+  let camera = UniformBuffer::new(&Camera {
+    view: glm::Mat4::identity().into(),
+    projection: glm::Mat4::identity().into(),
+  }).expect("Can't make camera buffer");
+  camera.bind_base(CAMERA_BINDING);
+
+  let mut tex_units = TextureUnits::new();
 
 
 
 
-  let mut model = glm::rotate(&glm::Mat4::identity(), -55.0_f32.to_radians(), &glm::vec3(1.0,0.0,0.0));
   let mut view: glm::Mat4;
   let mut projection: glm::Mat4;
 
@@ -173,6 +144,13 @@ unsafe {
 
     let cam_speed = 0.01 * delta_t;
 
+    if shaders.poll() {
+      println!("Reloaded shaders");
+      shaders.program().use_program();
+      setup_program(shaders.program(), &cube);
+    }
+    let shader_program = shaders.program();
+
     sdl.set_relative_mouse_mode(true).expect("Can't capture mouse");
 
     // handle events this frame
@@ -225,26 +203,45 @@ unsafe {
 
 
 
-    shader_program.setFloat("mix_lvl", vec![input]);
-    shader_program.setMat4("model", vec![model]);
-    shader_program.setMat4("view", vec![view]);
-    shader_program.setMat4("projection", vec![projection]);
+    camera.update(&Camera { view: view.into(), projection: projection.into() });
 
 
     clear();
-    for i in 0..10{
-      model = glm::Mat4::identity();
-      model = glm::translate(&model, &cube_pos[i]);
-      model = glm::rotate(&model, angle + i as f32, &glm::vec3(1.0,0.3,0.5));
-      shader_program.setMat4("model", vec![model]);
-      unsafe {
-        glDrawArrays(GL_TRIANGLES, 0, 36);
-      }
+    // the skybox left its own program in use last frame
+    shader_program.use_program();
+    shader_program.set_uniform("mix_lvl", &input).unwrap();
+    tex_units.reset();
+    tex_units.bind(shader_program, "brick", &tex).unwrap();
+    tex_units.bind(shader_program, "face", &tex2).unwrap();
+    let models: Vec<Instance> = cube_pos.iter().enumerate().map(|(i, pos)| {
+      let model = glm::translate(&glm::Mat4::identity(), pos);
+      let model = glm::rotate(&model, angle + i as f32, &glm::vec3(1.0,0.3,0.5));
+      Instance { model: model.into() }
+    }).collect();
+    cube_instances.update(&models);
+    cube.draw_instanced(cube_instances.len()).unwrap();
+    if let Some((skybox, sky)) = &skybox {
+      skybox.draw(&mut tex_units, sky, &view, &projection).unwrap();
     }
     win.swap_window();
   }
 }
 
+fn setup_program(program: &ShaderProgram, cube: &Mesh<PrimitiveVertex>) {
+    program.bind_uniform_block("Camera", CAMERA_BINDING).unwrap();
+    cube.validate(program).unwrap();
+}
+
+/// The six faces in `textures/skybox/`, named like the learnopengl.com ones.
+fn load_skybox() -> Result<(Skybox, CubeMap), Error> {
+    let sky = CubeMap::new()?;
+    sky.set_params(&TextureParams::new().wrap(Wrap::ClampToEdge));
+    let faces = ["right", "left", "top", "bottom", "front", "back"]
+        .map(|face| format!("textures/skybox/{}.jpg", face));
+    sky.load_faces(&faces, &LoadOptions::new().color_space(ColorSpace::Srgb))?;
+    Ok((Skybox::new()?, sky))
+}
+
 fn rotate_camera(yaw: &mut f32, pitch: &mut f32, x_delta: i32, y_delta: i32) -> glm::Vec3 {
     let sensitivity = 0.1;
     let x_delta = x_delta as f32 * sensitivity;
@@ -0,0 +1,309 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use bytemuck::Pod;
+use gl33::{*, global_loader::*};
+
+use super::{BufferType, Error, VertexArray};
+use super::buffer::{Buffer, Usage};
+use super::shader::ShaderProgram;
+use super::vertex::{Vertex, VertexLayout};
+
+/// What the vertices of a [`Mesh`] make up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(u32)]
+pub enum Primitive {
+    Points = GL_POINTS.0,
+    Lines = GL_LINES.0,
+    LineStrip = GL_LINE_STRIP.0,
+    LineLoop = GL_LINE_LOOP.0,
+    #[default]
+    Triangles = GL_TRIANGLES.0,
+    TriangleStrip = GL_TRIANGLE_STRIP.0,
+    TriangleFan = GL_TRIANGLE_FAN.0,
+}
+
+/// An element buffer in the smallest type that fits the biggest index.
+enum Indices {
+    U8(Buffer<u8>),
+    U16(Buffer<u16>),
+    U32(Buffer<u32>),
+}
+
+impl Indices {
+    fn new(indices: &[u32]) -> Result<Self, Error> {
+        Ok(match index_type(indices) {
+            GL_UNSIGNED_BYTE => {
+                let narrow: Vec<u8> = indices.iter().map(|&i| i as u8).collect();
+                Self::U8(Buffer::from_slice(BufferType::ElementArray, &narrow, Usage::StaticDraw)?)
+            }
+            GL_UNSIGNED_SHORT => {
+                let narrow: Vec<u16> = indices.iter().map(|&i| i as u16).collect();
+                Self::U16(Buffer::from_slice(BufferType::ElementArray, &narrow, Usage::StaticDraw)?)
+            }
+            _ => Self::U32(Buffer::from_slice(BufferType::ElementArray, indices, Usage::StaticDraw)?),
+        })
+    }
+
+    fn bind(&self) {
+        match self {
+            Self::U8(b) => b.bind(),
+            Self::U16(b) => b.bind(),
+            Self::U32(b) => b.bind(),
+        }
+    }
+
+    fn gl_type(&self) -> GLenum {
+        match self {
+            Self::U8(_) => GL_UNSIGNED_BYTE,
+            Self::U16(_) => GL_UNSIGNED_SHORT,
+            Self::U32(_) => GL_UNSIGNED_INT,
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::U8(b) => b.len(),
+            Self::U16(b) => b.len(),
+            Self::U32(b) => b.len(),
+        }
+    }
+}
+
+/// The smallest of `GL_UNSIGNED_BYTE`, `GL_UNSIGNED_SHORT` and
+/// `GL_UNSIGNED_INT` that holds every index.
+fn index_type(indices: &[u32]) -> GLenum {
+    match indices.iter().copied().max().unwrap_or(0) {
+        max if max <= u8::MAX as u32 => GL_UNSIGNED_BYTE,
+        max if max <= u16::MAX as u32 => GL_UNSIGNED_SHORT,
+        _ => GL_UNSIGNED_INT,
+    }
+}
+
+/// Vertices in a buffer, optionally indexed, with the [`VertexArray`] that
+/// reads them. Draw it with [`draw`](Self::draw).
+pub struct Mesh<V: Vertex> {
+    vao: VertexArray,
+    vertices: Buffer<V>,
+    indices: Option<Indices>,
+    primitive: Primitive,
+    /// Layouts of the instance buffers attached with
+    /// [`attach_instances`](Self::attach_instances).
+    instance_layouts: Vec<VertexLayout>,
+    /// Element counts of those buffers, kept up to date by the buffers.
+    instance_lens: Vec<Rc<Cell<usize>>>,
+}
+
+impl<V: Vertex> Mesh<V> {
+    /// A mesh drawn with `glDrawArrays`, every vertex in order.
+    pub fn from_vertices(vertices: &[V]) -> Result<Self, Error> {
+        Self::new(vertices, None)
+    }
+
+    /// A mesh drawn with `glDrawElements`. The indices are stored as `u8`,
+    /// `u16` or `u32`, whichever is the smallest that fits.
+    pub fn indexed(vertices: &[V], indices: &[u32]) -> Result<Self, Error> {
+        Self::new(vertices, Some(indices))
+    }
+
+    fn new(vertices: &[V], indices: Option<&[u32]>) -> Result<Self, Error> {
+        if let Some(&bad) = indices.and_then(|i| i.iter().find(|&&i| i as usize >= vertices.len())) {
+            return Err(Error::BufferRange { start: bad as usize, end: bad as usize + 1, len: vertices.len() });
+        }
+        let vao = VertexArray::new()?;
+        let vertices = Buffer::from_slice(BufferType::Array, vertices, Usage::StaticDraw)?;
+        let indices = indices.map(Indices::new).transpose()?;
+        vao.set_layout(vertices.buffer(), &V::layout());
+        // the element buffer binding is part of the VAO
+        if let Some(indices) = &indices {
+            indices.bind();
+        }
+        VertexArray::clear_bind();
+        Ok(Self { vao, vertices, indices, primitive: Primitive::Triangles, instance_layouts: Vec::new(), instance_lens: Vec::new() })
+    }
+
+    /// Triangles by default.
+    pub fn primitive(self, primitive: Primitive) -> Self {
+        Self { primitive, ..self }
+    }
+
+    /// Draws the whole mesh with whatever program is in use. Leaves the
+    /// mesh's VAO bound.
+    pub fn draw(&self) {
+        self.vao.bind();
+        let mode = GLenum(self.primitive as u32);
+        unsafe {
+            match &self.indices {
+                Some(indices) => glDrawElements(mode, indices.len() as i32, indices.gl_type(), std::ptr::null()),
+                None => glDrawArrays(mode, 0, self.vertices.len() as i32),
+            }
+        }
+    }
+
+    /// Draws `count` instances of the whole mesh, each reading the next
+    /// element of the attached [`InstanceBuffer`]s. Leaves the mesh's VAO
+    /// bound.
+    ///
+    /// Errors without drawing if an attached buffer has fewer than `count`
+    /// elements.
+    pub fn draw_instanced(&self, count: usize) -> Result<(), Error> {
+        if let Some(len) = self.instance_lens.iter().map(|len| len.get()).find(|&len| len < count) {
+            return Err(Error::BufferRange { start: 0, end: count, len });
+        }
+        self.vao.bind();
+        let mode = GLenum(self.primitive as u32);
+        unsafe {
+            match &self.indices {
+                Some(indices) => glDrawElementsInstanced(
+                    mode,
+                    indices.len() as i32,
+                    indices.gl_type(),
+                    std::ptr::null(),
+                    count as i32,
+                ),
+                None => glDrawArraysInstanced(mode, 0, self.vertices.len() as i32, count as i32),
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads per instance attributes from `instances`, on the locations
+    /// after the ones `V` and any earlier instance buffers use. The buffer can
+    /// be updated afterwards without attaching it again.
+    pub fn attach_instances<I: Vertex>(&mut self, instances: &InstanceBuffer<I>) {
+        let first = self.instance_layouts.iter().fold(V::layout().next_location(), |next, l| next.max(l.next_location()));
+        let layout = I::layout().at_location(first).divisor(1);
+        self.vao.set_layout(instances.buffer.buffer(), &layout);
+        VertexArray::clear_bind();
+        self.instance_layouts.push(layout);
+        self.instance_lens.push(Rc::clone(&instances.len));
+    }
+
+    /// Checks `V`'s layout, and those of attached instance buffers, against
+    /// the program's inputs, see
+    /// [`VertexLayout::validate`](crate::vertex::VertexLayout::validate).
+    pub fn validate(&self, program: &ShaderProgram) -> Result<(), Error> {
+        let mut layout = V::layout();
+        for instance in &self.instance_layouts {
+            layout.attributes.extend_from_slice(&instance.attributes);
+        }
+        layout.validate(program)
+    }
+
+    /// Overwrites vertices starting at `offset`, the count can't change.
+    pub fn update_vertices(&self, offset: usize, vertices: &[V]) -> Result<(), Error> {
+        self.vertices.update(offset, vertices)
+    }
+
+    pub fn vao(&self) -> &VertexArray {
+        &self.vao
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.vertices.len()
+    }
+
+    /// `None` for meshes without indices.
+    pub fn index_count(&self) -> Option<usize> {
+        self.indices.as_ref().map(Indices::len)
+    }
+
+    /// `GL_UNSIGNED_BYTE`, `GL_UNSIGNED_SHORT` or `GL_UNSIGNED_INT`, `None`
+    /// for meshes without indices.
+    pub fn index_type(&self) -> Option<GLenum> {
+        self.indices.as_ref().map(Indices::gl_type)
+    }
+}
+
+/// Per instance data for [`Mesh::draw_instanced`], eg a model matrix for
+/// every copy of the mesh. Attach it to meshes with
+/// [`Mesh::attach_instances`].
+pub struct InstanceBuffer<I: Vertex> {
+    buffer: Buffer<I>,
+    /// Shared with the meshes it's attached to, so they can check the
+    /// instance count.
+    len: Rc<Cell<usize>>,
+}
+
+impl<I: Vertex> InstanceBuffer<I> {
+    /// An empty buffer, meant to be rewritten every frame.
+    pub fn new() -> Result<Self, Error> {
+        Self::with_usage(Usage::StreamDraw)
+    }
+
+    /// [`Usage::StaticDraw`] for instances that never move.
+    pub fn with_usage(usage: Usage) -> Result<Self, Error> {
+        Ok(Self { buffer: Buffer::new(BufferType::Array, usage)?, len: Rc::new(Cell::new(0)) })
+    }
+
+    /// Replaces every instance. The old data is orphaned rather than
+    /// overwritten, so this doesn't wait on draws still reading it.
+    pub fn update(&mut self, instances: &[I]) {
+        self.buffer.set_data(instances);
+        self.len.set(instances.len());
+    }
+
+    /// Overwrites instances starting at `offset`, the count can't change.
+    pub fn update_range(&self, offset: usize, instances: &[I]) -> Result<(), Error> {
+        self.buffer.update(offset, instances)
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+}
+
+/// Turns a plain list of vertices into unique vertices plus indices, for
+/// [`Mesh::indexed`]. Vertices are the same if their bytes are, so `0.0` and
+/// `-0.0` count as different.
+pub fn dedup_vertices<V: Pod>(vertices: &[V]) -> (Vec<V>, Vec<u32>) {
+    let mut seen: HashMap<&[u8], u32> = HashMap::new();
+    let mut unique = Vec::new();
+    let indices = vertices
+        .iter()
+        .map(|v| {
+            *seen.entry(bytemuck::bytes_of(v)).or_insert_with(|| {
+                unique.push(*v);
+                unique.len() as u32 - 1
+            })
+        })
+        .collect();
+    (unique, indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn index_width_boundaries() {
+        assert_eq!(index_type(&[]), GL_UNSIGNED_BYTE);
+        assert_eq!(index_type(&[0, 255, 3]), GL_UNSIGNED_BYTE);
+        assert_eq!(index_type(&[0, 256, 3]), GL_UNSIGNED_SHORT);
+        assert_eq!(index_type(&[65535]), GL_UNSIGNED_SHORT);
+        assert_eq!(index_type(&[1, 65536]), GL_UNSIGNED_INT);
+        assert_eq!(index_type(&[u32::MAX]), GL_UNSIGNED_INT);
+    }
+
+    #[test]
+    fn dedup_keeps_first_seen_order() {
+        let (unique, indices) = dedup_vertices(&[[3.0f32, 0.0], [1.0, 0.0], [3.0, 0.0], [2.0, 0.0], [1.0, 0.0]]);
+        assert_eq!(unique, [[3.0, 0.0], [1.0, 0.0], [2.0, 0.0]]);
+        assert_eq!(indices, [0, 1, 0, 2, 1]);
+    }
+
+    #[test]
+    fn dedup_compares_bits() {
+        // 0.0 and -0.0 are equal floats but different vertices, normals
+        // pointing opposite ways mustn't merge
+        let (unique, indices) = dedup_vertices(&[[0.0f32], [-0.0], [0.0]]);
+        assert_eq!(unique.len(), 2);
+        assert!(unique[1][0].is_sign_negative());
+        assert_eq!(indices, [0, 1, 0]);
+    }
+}
@@ -0,0 +1,322 @@
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::Error;
+
+/// One file that went into a preprocessed shader.
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    pub path: PathBuf,
+    pub text: String,
+}
+
+/// The output of [`preprocess_file`].
+///
+/// `files[n]` is the file the driver calls source string `n`, because the
+/// `#line` directives in `source` are numbered that way.
+#[derive(Debug, Clone)]
+pub struct Preprocessed {
+    /// The expanded source to hand to the driver.
+    pub source: String,
+    /// Every file that was read, the root file is always first.
+    pub files: Vec<SourceFile>,
+}
+
+/// Reads a shader file and expands it for the driver.
+///
+/// * `#include "file"` is resolved relative to the including file. Files that
+///   use `#pragma once` or a classic `#ifndef`/`#define` guard are only pasted
+///   in once, and an include cycle is an error.
+/// * `defines` are inserted as `#define name value` right after `#version`.
+/// * `#line` directives are emitted around every include so driver errors
+///   still point at the right file and line.
+pub fn preprocess_file(path: impl AsRef<Path>, defines: &[(&str, &str)]) -> Result<Preprocessed, Error> {
+    let mut state = State {
+        out: String::new(),
+        files: Vec::new(),
+        stack: Vec::new(),
+        once: HashSet::new(),
+        guards: HashSet::new(),
+    };
+    let path = path.as_ref();
+    let text = fs::read_to_string(path)?;
+    state.expand(path.to_path_buf(), text, Some(defines))?;
+    Ok(Preprocessed { source: state.out, files: state.files })
+}
+
+struct State {
+    out: String,
+    files: Vec<SourceFile>,
+    /// Canonical paths of the files currently being expanded.
+    stack: Vec<PathBuf>,
+    /// Files that said `#pragma once`.
+    once: HashSet<PathBuf>,
+    /// Guard macros that have been defined by an expanded file.
+    guards: HashSet<String>,
+}
+
+impl State {
+    /// Appends `text` to the output. `defines` is only `Some` for the root file.
+    fn expand(&mut self, path: PathBuf, text: String, defines: Option<&[(&str, &str)]>) -> Result<(), Error> {
+        let canonical = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+        if let Some(guard) = include_guard(&text) {
+            self.guards.insert(guard);
+        }
+
+        let index = match self.files.iter().position(|f| f.path == path) {
+            Some(i) => i,
+            None => {
+                self.files.push(SourceFile { path: path.clone(), text: text.clone() });
+                self.files.len() - 1
+            }
+        };
+        self.stack.push(canonical.clone());
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+        let mut pending_defines = defines;
+        if let Some(defines) = pending_defines {
+            if !text.lines().any(|l| directive(l, "version").is_some()) {
+                write_defines(&mut self.out, defines, 1, index);
+                pending_defines = None;
+            }
+        }
+
+        for (i, line) in text.lines().enumerate() {
+            let line_no = i + 1;
+            if directive(line, "version").is_some() {
+                if let Some(defines) = pending_defines.take() {
+                    self.out.push_str(line);
+                    self.out.push('\n');
+                    write_defines(&mut self.out, defines, line_no + 1, index);
+                } else {
+                    // only the root file gets to pick the version
+                    self.out.push('\n');
+                }
+            } else if directive(line, "pragma").is_some_and(|rest| rest.trim() == "once") {
+                self.once.insert(canonical.clone());
+                self.out.push('\n');
+            } else if let Some(rest) = directive(line, "include") {
+                let name = rest
+                    .trim()
+                    .strip_prefix('"')
+                    .and_then(|r| r.strip_suffix('"'))
+                    .ok_or_else(|| Error::Preprocess {
+                        file: path.clone(),
+                        line: line_no,
+                        message: format!("expected #include \"file\", found `{}`", line.trim()),
+                    })?;
+                let include_path = dir.join(name);
+                let include_canonical = fs::canonicalize(&include_path).unwrap_or_else(|_| include_path.clone());
+                if self.stack.contains(&include_canonical) {
+                    let chain: Vec<String> = self
+                        .stack
+                        .iter()
+                        .chain(Some(&include_canonical))
+                        .map(|p| p.display().to_string())
+                        .collect();
+                    return Err(Error::Preprocess {
+                        file: path.clone(),
+                        line: line_no,
+                        message: format!("include cycle: {}", chain.join(" -> ")),
+                    });
+                }
+                if self.once.contains(&include_canonical) {
+                    self.out.push('\n');
+                    continue;
+                }
+                let include_text = fs::read_to_string(&include_path).map_err(|e| Error::Preprocess {
+                    file: path.clone(),
+                    line: line_no,
+                    message: format!("can't read include `{}`: {}", include_path.display(), e),
+                })?;
+                if include_guard(&include_text).is_some_and(|g| self.guards.contains(&g)) {
+                    self.out.push('\n');
+                    continue;
+                }
+                let include_index = self
+                    .files
+                    .iter()
+                    .position(|f| f.path == include_path)
+                    .unwrap_or(self.files.len());
+                let _ = writeln!(self.out, "#line 1 {}", include_index);
+                self.expand(include_path, include_text, None)?;
+                let _ = writeln!(self.out, "#line {} {}", line_no + 1, index);
+            } else {
+                self.out.push_str(line);
+                self.out.push('\n');
+            }
+        }
+
+        self.stack.pop();
+        Ok(())
+    }
+}
+
+fn write_defines(out: &mut String, defines: &[(&str, &str)], next_line: usize, index: usize) {
+    if defines.is_empty() {
+        return;
+    }
+    for (name, value) in defines {
+        let _ = writeln!(out, "#define {} {}", name, value);
+    }
+    let _ = writeln!(out, "#line {} {}", next_line, index);
+}
+
+/// If `line` is the preprocessor directive `name`, returns what follows it.
+fn directive<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let rest = line.trim_start().strip_prefix('#')?.trim_start().strip_prefix(name)?;
+    if rest.is_empty() || rest.starts_with(char::is_whitespace) {
+        Some(rest)
+    } else {
+        None
+    }
+}
+
+/// Finds the macro of a classic `#ifndef X` / `#define X` include guard at the
+/// top of a file.
+fn include_guard(text: &str) -> Option<String> {
+    let mut lines = text
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with("//"));
+    let guard = directive(lines.next()?, "ifndef")?.trim();
+    let defined = directive(lines.next()?, "define")?.trim();
+    if !guard.is_empty() && guard == defined {
+        Some(guard.to_string())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory holding `files`, returns its path.
+    fn write_files(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("preprocess-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for (name, text) in files {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn includes_relative_to_the_including_file() {
+        let dir = write_files(
+            "relative",
+            &[
+                ("main.frag", "#version 330 core\n#include \"lib/a.glsl\"\nvoid main() {}\n"),
+                ("lib/a.glsl", "#include \"b.glsl\"\nfloat a;\n"),
+                ("lib/b.glsl", "float b;\n"),
+            ],
+        );
+        let out = preprocess_file(dir.join("main.frag"), &[]).unwrap();
+        assert_eq!(
+            out.source,
+            "#version 330 core\n#line 1 1\n#line 1 2\nfloat b;\n#line 2 1\nfloat a;\n#line 3 0\nvoid main() {}\n"
+        );
+        let names: Vec<_> = out.files.iter().map(|f| f.path.strip_prefix(&dir).unwrap().to_path_buf()).collect();
+        assert_eq!(names, [PathBuf::from("main.frag"), PathBuf::from("lib/a.glsl"), PathBuf::from("lib/b.glsl")]);
+    }
+
+    #[test]
+    fn line_directives_map_back_to_files() {
+        let dir = write_files(
+            "lines",
+            &[
+                ("main.frag", "#version 330 core\n#include \"a.glsl\"\nfloat x;\n"),
+                ("a.glsl", "float a;\nfloat a2;\n"),
+            ],
+        );
+        let out = preprocess_file(dir.join("main.frag"), &[]).unwrap();
+        // follow the #line directives like the driver would
+        let (mut file, mut line) = (0, 1);
+        let mut seen = Vec::new();
+        for text in out.source.lines() {
+            if let Some(rest) = directive(text, "line") {
+                let mut parts = rest.split_whitespace().map(|p| p.parse::<usize>().unwrap());
+                line = parts.next().unwrap();
+                file = parts.next().unwrap();
+                continue;
+            }
+            if !text.is_empty() {
+                seen.push((text.to_string(), file, line));
+            }
+            line += 1;
+        }
+        let expected = [("#version 330 core", 0, 1), ("float a;", 1, 1), ("float a2;", 1, 2), ("float x;", 0, 3)];
+        let expected: Vec<_> = expected.iter().map(|&(t, f, l)| (t.to_string(), f, l)).collect();
+        assert_eq!(seen, expected);
+    }
+
+    #[test]
+    fn defines_go_after_version() {
+        let dir = write_files("defines", &[("main.frag", "// header\n#version 330 core\nvoid main() {}\n")]);
+        let out = preprocess_file(dir.join("main.frag"), &[("LIGHTS", "4"), ("SHADOWS", "1")]).unwrap();
+        assert_eq!(
+            out.source,
+            "// header\n#version 330 core\n#define LIGHTS 4\n#define SHADOWS 1\n#line 3 0\nvoid main() {}\n"
+        );
+    }
+
+    #[test]
+    fn defines_without_version_go_first() {
+        let dir = write_files("defines-no-version", &[("main.frag", "void main() {}\n")]);
+        let out = preprocess_file(dir.join("main.frag"), &[("A", "1")]).unwrap();
+        assert_eq!(out.source, "#define A 1\n#line 1 0\nvoid main() {}\n");
+    }
+
+    #[test]
+    fn pragma_once_and_guards_include_once() {
+        let dir = write_files(
+            "once",
+            &[
+                (
+                    "main.frag",
+                    "#include \"once.glsl\"\n#include \"guarded.glsl\"\n#include \"once.glsl\"\n#include \"guarded.glsl\"\n",
+                ),
+                ("once.glsl", "#pragma once\nfloat once;\n"),
+                ("guarded.glsl", "#ifndef GUARDED\n#define GUARDED\nfloat guarded;\n#endif\n"),
+            ],
+        );
+        let out = preprocess_file(dir.join("main.frag"), &[]).unwrap();
+        assert_eq!(out.source.matches("float once;").count(), 1);
+        assert_eq!(out.source.matches("float guarded;").count(), 1);
+        assert_eq!(out.files.len(), 3);
+    }
+
+    #[test]
+    fn include_cycle_is_an_error() {
+        let dir = write_files(
+            "cycle",
+            &[
+                ("main.frag", "#include \"a.glsl\"\n"),
+                ("a.glsl", "#include \"b.glsl\"\n"),
+                ("b.glsl", "\n#include \"a.glsl\"\n"),
+            ],
+        );
+        match preprocess_file(dir.join("main.frag"), &[]) {
+            Err(Error::Preprocess { file, line, message }) => {
+                assert_eq!(file, dir.join("b.glsl"));
+                assert_eq!(line, 2);
+                assert!(message.starts_with("include cycle:"), "{}", message);
+            }
+            other => panic!("expected an include cycle, got {:?}", other.map(|p| p.source)),
+        }
+    }
+
+    #[test]
+    fn missing_include_is_an_error() {
+        let dir = write_files("missing", &[("main.frag", "#include \"nope.glsl\"\n")]);
+        assert!(matches!(
+            preprocess_file(dir.join("main.frag"), &[]),
+            Err(Error::Preprocess { line: 1, .. })
+        ));
+    }
+}
@@ -0,0 +1,479 @@
+//! Meshes made on the CPU: cubes, spheres, cylinders and friends.
+//!
+//! Every generator returns [`MeshData`], plain vectors that can be checked or
+//! changed without a GL context, and [`MeshData::upload`] turns that into a
+//! [`Mesh`]. Shapes are centred on the origin with +Y up, front faces wind
+//! counter clockwise seen from outside, and texture coordinates have `v`
+//! going up like every other texture here.
+//!
+//! ```ignore
+//! let sphere = primitives::uv_sphere(0.5, 32, 16).upload()?;
+//! sphere.draw();
+//! ```
+
+use std::collections::HashMap;
+use std::f32::consts::{PI, TAU};
+
+use nalgebra_glm as glm;
+
+use super::Error;
+use super::mesh::Mesh;
+
+crate::vertex_struct! {
+    /// The vertex every primitive is made of. `tangent.w` is the handedness,
+    /// the bitangent is `cross(normal, tangent.xyz) * tangent.w`.
+    ///
+    /// Locations are 0 for the position, 1 the normal, 2 the texture
+    /// coordinate and 3 the tangent.
+    #[derive(Debug, PartialEq)]
+    pub struct PrimitiveVertex {
+        pub position: [f32; 3],
+        pub normal: [f32; 3],
+        pub uv: [f32; 2],
+        pub tangent: [f32; 4],
+    }
+}
+
+/// Indexed triangles, see the [module docs](self).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MeshData {
+    pub vertices: Vec<PrimitiveVertex>,
+    /// Three per triangle.
+    pub indices: Vec<u32>,
+}
+
+impl MeshData {
+    /// Copies the data into GL buffers.
+    pub fn upload(&self) -> Result<Mesh<PrimitiveVertex>, Error> {
+        Mesh::indexed(&self.vertices, &self.indices)
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    /// Works out every tangent from the positions and texture coordinates.
+    /// The generators already call this, it's for data that has been edited.
+    pub fn compute_tangents(&mut self) {
+        let mut tangents = vec![glm::Vec3::zeros(); self.vertices.len()];
+        let mut bitangents = vec![glm::Vec3::zeros(); self.vertices.len()];
+        for tri in self.indices.chunks_exact(3) {
+            let [a, b, c] = [tri[0], tri[1], tri[2]].map(|i| &self.vertices[i as usize]);
+            let e1 = glm::Vec3::from(b.position) - glm::Vec3::from(a.position);
+            let e2 = glm::Vec3::from(c.position) - glm::Vec3::from(a.position);
+            let (du1, dv1) = (b.uv[0] - a.uv[0], b.uv[1] - a.uv[1]);
+            let (du2, dv2) = (c.uv[0] - a.uv[0], c.uv[1] - a.uv[1]);
+            let det = du1 * dv2 - du2 * dv1;
+            if det.abs() < 1e-12 {
+                continue;
+            }
+            let tangent = (e1 * dv2 - e2 * dv1) / det;
+            let bitangent = (e2 * du1 - e1 * du2) / det;
+            for &i in tri {
+                tangents[i as usize] += tangent;
+                bitangents[i as usize] += bitangent;
+            }
+        }
+        for ((v, t), b) in self.vertices.iter_mut().zip(tangents).zip(bitangents) {
+            let n = glm::Vec3::from(v.normal);
+            // Gram-Schmidt, keep only the part along the surface
+            let mut t = t - n * n.dot(&t);
+            if t.norm() < 1e-6 {
+                // no usable texture direction, any one along the surface will do
+                let axis = if n.x.abs() < 0.9 { glm::vec3(1.0, 0.0, 0.0) } else { glm::vec3(0.0, 1.0, 0.0) };
+                t = axis - n * n.dot(&axis);
+            }
+            let t = t.normalize();
+            let w = if n.cross(&t).dot(&b) < 0.0 { -1.0 } else { 1.0 };
+            v.tangent = [t.x, t.y, t.z, w];
+        }
+    }
+
+    fn push(&mut self, position: glm::Vec3, normal: glm::Vec3, uv: [f32; 2]) -> u32 {
+        self.vertices.push(PrimitiveVertex { position: position.into(), normal: normal.into(), uv, tangent: [0.0; 4] });
+        self.vertices.len() as u32 - 1
+    }
+
+    /// Adds a triangle unless two of its corners are in the same place, like
+    /// the ones squeezed into the poles of a sphere.
+    fn triangle(&mut self, a: u32, b: u32, c: u32) {
+        let p = |i: u32| glm::Vec3::from(self.vertices[i as usize].position);
+        if (p(b) - p(a)).cross(&(p(c) - p(a))).norm() > 1e-10 {
+            self.indices.extend([a, b, c]);
+        }
+    }
+
+    /// A grid of quads from a `(columns + 1) * (rows + 1)` block of vertices
+    /// starting at `first`, laid out row by row. The quad's front is the side
+    /// where going along a row then up a column turns counter clockwise.
+    fn quads(&mut self, first: u32, columns: u32, rows: u32) {
+        let row = columns + 1;
+        for j in 0..rows {
+            for i in 0..columns {
+                let a = first + j * row + i;
+                let (b, c, d) = (a + 1, a + row + 1, a + row);
+                self.triangle(a, b, c);
+                self.triangle(a, c, d);
+            }
+        }
+    }
+
+    /// A flat grid from `origin` spanning `u` and `v`, facing `u × v`.
+    fn grid(&mut self, origin: glm::Vec3, u: glm::Vec3, v: glm::Vec3, columns: u32, rows: u32) {
+        let normal = u.cross(&v).normalize();
+        let first = self.vertices.len() as u32;
+        for j in 0..=rows {
+            for i in 0..=columns {
+                let (s, t) = (i as f32 / columns as f32, j as f32 / rows as f32);
+                self.push(origin + u * s + v * t, normal, [s, t]);
+            }
+        }
+        self.quads(first, columns, rows);
+    }
+
+    /// Spins a profile around the Y axis. Each profile point is
+    /// `(radius, y, normal radius, normal y, v)`, listed bottom to top along
+    /// the outside. `u` goes once around, starting at +Z towards +X.
+    fn lathe(&mut self, segments: u32, profile: &[(f32, f32, f32, f32, f32)]) {
+        let first = self.vertices.len() as u32;
+        for &(radius, y, normal_radius, normal_y, v) in profile {
+            for i in 0..=segments {
+                let s = i as f32 / segments as f32;
+                let (sin, cos) = (s * TAU).sin_cos();
+                let position = glm::vec3(radius * sin, y, radius * cos);
+                let normal = glm::vec3(normal_radius * sin, normal_y, normal_radius * cos).normalize();
+                self.push(position, normal, [s, v]);
+            }
+        }
+        self.quads(first, segments, profile.len() as u32 - 1);
+    }
+
+    /// A flat disc at height `y` facing straight up or down.
+    fn disc(&mut self, y: f32, radius: f32, segments: u32, up: bool) {
+        let normal = glm::vec3(0.0, if up { 1.0 } else { -1.0 }, 0.0);
+        let center = self.push(glm::vec3(0.0, y, 0.0), normal, [0.5, 0.5]);
+        for i in 0..=segments {
+            let (sin, cos) = (i as f32 / segments as f32 * TAU).sin_cos();
+            // mirrored underneath so the texture isn't backwards from below
+            let u = if up { 0.5 + 0.5 * sin } else { 0.5 - 0.5 * sin };
+            self.push(glm::vec3(radius * sin, y, radius * cos), normal, [u, 0.5 - 0.5 * cos]);
+        }
+        for i in 0..segments {
+            let (a, b) = (center + 1 + i, center + 2 + i);
+            if up {
+                self.triangle(center, a, b);
+            } else {
+                self.triangle(center, b, a);
+            }
+        }
+    }
+
+    fn finish(mut self) -> Self {
+        self.compute_tangents();
+        self
+    }
+}
+
+/// A cube `size` wide, each face with the whole texture on it.
+pub fn cube(size: f32) -> MeshData {
+    let h = size / 2.0;
+    let mut data = MeshData::default();
+    // (normal, u, v) with u × v = normal, so every face comes out front side out
+    let faces = [
+        ([1.0, 0.0, 0.0], [0.0, 0.0, -1.0], [0.0, 1.0, 0.0]),
+        ([-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+        ([0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, -1.0]),
+        ([0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+        ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        ([0.0, 0.0, -1.0], [-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+    ];
+    for (n, u, v) in faces.map(|(n, u, v)| (glm::Vec3::from(n), glm::Vec3::from(u), glm::Vec3::from(v))) {
+        data.grid((n - u - v) * h, u * size, v * size, 1, 1);
+    }
+    data.finish()
+}
+
+/// A flat `width` by `depth` rectangle on the XZ plane facing +Y, split into
+/// `columns` by `rows` quads. `u` runs along +X and `v` along -Z.
+pub fn plane(width: f32, depth: f32, columns: u32, rows: u32) -> MeshData {
+    let mut data = MeshData::default();
+    let origin = glm::vec3(-width / 2.0, 0.0, depth / 2.0);
+    data.grid(origin, glm::vec3(width, 0.0, 0.0), glm::vec3(0.0, 0.0, -depth), columns.max(1), rows.max(1));
+    data.finish()
+}
+
+/// A sphere of `segments` slices around and `rings` stacks from pole to
+/// pole. The texture wraps around once like a world map.
+pub fn uv_sphere(radius: f32, segments: u32, rings: u32) -> MeshData {
+    let rings = rings.max(2);
+    let profile: Vec<_> = (0..=rings)
+        .map(|k| {
+            let v = k as f32 / rings as f32;
+            let (sin, cos) = (PI * (1.0 - v)).sin_cos();
+            // exactly 0 at the poles so the triangles there get dropped
+            let sin = if k == 0 || k == rings { 0.0 } else { sin };
+            (radius * sin, radius * cos, sin, cos, v)
+        })
+        .collect();
+    let mut data = MeshData::default();
+    data.lathe(segments.max(3), &profile);
+    data.finish()
+}
+
+/// A sphere made by splitting each triangle of an icosahedron into four
+/// `subdivisions` times, so the triangles are close to even everywhere.
+/// 0 gives the plain icosahedron, each step has four times the triangles.
+///
+/// Texture coordinates map like [`uv_sphere`]'s, vertices on the seam are
+/// doubled so the texture doesn't run backwards across it, and every
+/// triangle touching a pole gets its own copy of the pole vertex.
+pub fn icosphere(radius: f32, subdivisions: u32) -> MeshData {
+    let t = (1.0 + 5f32.sqrt()) / 2.0;
+    let mut points: Vec<glm::Vec3> = [
+        [-1.0, t, 0.0], [1.0, t, 0.0], [-1.0, -t, 0.0], [1.0, -t, 0.0],
+        [0.0, -1.0, t], [0.0, 1.0, t], [0.0, -1.0, -t], [0.0, 1.0, -t],
+        [t, 0.0, -1.0], [t, 0.0, 1.0], [-t, 0.0, -1.0], [-t, 0.0, 1.0],
+    ]
+    .into_iter()
+    .map(|p| glm::Vec3::from(p).normalize())
+    .collect();
+    let mut triangles: Vec<[u32; 3]> = vec![
+        [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+        [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+        [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+        [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        // each edge is split once, whichever triangle gets to it first
+        let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+        let mut midpoint = |a: u32, b: u32| {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                points.push(((points[a as usize] + points[b as usize]) / 2.0).normalize());
+                points.len() as u32 - 1
+            })
+        };
+        triangles = triangles
+            .into_iter()
+            .flat_map(|[a, b, c]| {
+                let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            })
+            .collect();
+    }
+
+    let mut data = MeshData::default();
+    // same mapping as the uv sphere, u starts at +Z and goes towards +X
+    let uv = |p: &glm::Vec3| [(p.x.atan2(p.z) / TAU).rem_euclid(1.0), 0.5 + p.y.clamp(-1.0, 1.0).asin() / PI];
+    for p in &points {
+        data.push(p * radius, *p, uv(p));
+    }
+    let is_pole = |p: &glm::Vec3| p.x.abs() < 1e-6 && p.z.abs() < 1e-6;
+    let poles: Vec<bool> = points.iter().map(is_pole).collect();
+    let mut doubled: HashMap<u32, u32> = HashMap::new();
+    for tri in triangles {
+        let max = tri
+            .iter()
+            .filter(|&&i| !poles[i as usize])
+            .map(|&i| data.vertices[i as usize].uv[0])
+            .fold(0.0, f32::max);
+        // corners wrapped round to the start of the texture get a copy
+        // further along instead
+        let tri = tri.map(|i| {
+            let vertex = data.vertices[i as usize];
+            if !poles[i as usize] && max - vertex.uv[0] > 0.5 {
+                *doubled.entry(i).or_insert_with(|| {
+                    let mut copy = vertex;
+                    copy.uv[0] += 1.0;
+                    data.vertices.push(copy);
+                    data.vertices.len() as u32 - 1
+                })
+            } else {
+                i
+            }
+        });
+        // u means nothing at a pole, so each triangle gets its own pole
+        // vertex with u halfway between the other two corners
+        let us = tri.map(|i| data.vertices[i as usize].uv[0]);
+        let tri = tri.map(|i| {
+            // seam copies come after `points` and are never poles
+            if poles.get(i as usize).copied().unwrap_or(false) {
+                let mut copy = data.vertices[i as usize];
+                copy.uv[0] = tri.iter().zip(us).filter(|&(&j, _)| j != i).map(|(_, u)| u).sum::<f32>() / 2.0;
+                data.vertices.push(copy);
+                data.vertices.len() as u32 - 1
+            } else {
+                i
+            }
+        });
+        data.triangle(tri[0], tri[1], tri[2]);
+    }
+    data.finish()
+}
+
+/// A cylinder `height` tall centred on the origin, capped at both ends.
+pub fn cylinder(radius: f32, height: f32, segments: u32) -> MeshData {
+    let h = height / 2.0;
+    let segments = segments.max(3);
+    let mut data = MeshData::default();
+    data.lathe(segments, &[(radius, -h, 1.0, 0.0, 0.0), (radius, h, 1.0, 0.0, 1.0)]);
+    data.disc(h, radius, segments, true);
+    data.disc(-h, radius, segments, false);
+    data.finish()
+}
+
+/// A cone with its point up and a capped base. The tip has one vertex per
+/// segment so each side keeps its own normal.
+pub fn cone(radius: f32, height: f32, segments: u32) -> MeshData {
+    let h = height / 2.0;
+    let segments = segments.max(3);
+    let slant = (height * height + radius * radius).sqrt();
+    let (normal_radius, normal_y) = (height / slant, radius / slant);
+    let mut data = MeshData::default();
+    data.lathe(segments, &[(radius, -h, normal_radius, normal_y, 0.0), (0.0, h, normal_radius, normal_y, 1.0)]);
+    data.disc(-h, radius, segments, false);
+    data.finish()
+}
+
+/// A ring `major_radius` from the centre to the middle of the tube, lying on
+/// the XZ plane. `u` goes around the ring and `v` around the tube.
+pub fn torus(major_radius: f32, minor_radius: f32, major_segments: u32, minor_segments: u32) -> MeshData {
+    let minor_segments = minor_segments.max(3);
+    let profile: Vec<_> = (0..=minor_segments)
+        .map(|k| {
+            let v = k as f32 / minor_segments as f32;
+            let (sin, cos) = (v * TAU).sin_cos();
+            (major_radius + minor_radius * cos, minor_radius * sin, cos, sin, v)
+        })
+        .collect();
+    let mut data = MeshData::default();
+    data.lathe(major_segments.max(3), &profile);
+    data.finish()
+}
+
+/// A cylinder `height` tall with a half sphere on each end, so
+/// `height + 2 * radius` tall in all. `rings` is per half sphere. `v` is
+/// spread by distance along the surface so the texture doesn't stretch.
+pub fn capsule(radius: f32, height: f32, segments: u32, rings: u32) -> MeshData {
+    let h = height / 2.0;
+    let rings = rings.max(1);
+    let length = PI * radius + height;
+    let mut profile = Vec::with_capacity(2 * rings as usize + 2);
+    for k in 0..=rings {
+        // from the bottom pole up to the equator
+        let angle = PI / 2.0 * k as f32 / rings as f32;
+        let (sin, cos) = angle.sin_cos();
+        profile.push((radius * sin, -h - radius * cos, sin, -cos, radius * angle / length));
+    }
+    for k in 0..=rings {
+        // from the equator up to the top pole
+        let angle = PI / 2.0 * k as f32 / rings as f32;
+        let (sin, cos) = angle.sin_cos();
+        let cos = if k == rings { 0.0 } else { cos };
+        profile.push((radius * cos, h + radius * sin, cos, sin, (PI / 2.0 * radius + height + radius * angle) / length));
+    }
+    let mut data = MeshData::default();
+    data.lathe(segments.max(3), &profile);
+    data.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shapes() -> Vec<(&'static str, MeshData)> {
+        vec![
+            ("cube", cube(2.0)),
+            ("plane", plane(2.0, 3.0, 4, 2)),
+            ("uv_sphere", uv_sphere(1.5, 16, 8)),
+            ("icosphere", icosphere(1.5, 2)),
+            ("cylinder", cylinder(0.5, 2.0, 12)),
+            ("cone", cone(0.5, 2.0, 12)),
+            ("torus", torus(1.0, 0.25, 16, 8)),
+            ("capsule", capsule(0.5, 1.0, 12, 4)),
+        ]
+    }
+
+    fn position(data: &MeshData, i: u32) -> glm::Vec3 {
+        glm::Vec3::from(data.vertices[i as usize].position)
+    }
+
+    #[test]
+    fn indices_are_whole_triangles_in_range() {
+        for (name, data) in shapes() {
+            assert!(data.triangle_count() > 0, "{}", name);
+            assert_eq!(data.indices.len() % 3, 0, "{}", name);
+            assert!(data.indices.iter().all(|&i| (i as usize) < data.vertices.len()), "{}", name);
+        }
+    }
+
+    #[test]
+    fn triangles_wind_counter_clockwise_from_outside() {
+        for (name, data) in shapes() {
+            for tri in data.indices.chunks_exact(3) {
+                let [a, b, c] = [tri[0], tri[1], tri[2]].map(|i| position(&data, i));
+                let face = (b - a).cross(&(c - a));
+                // agrees with the vertex normals, which point away from the surface
+                let normals: glm::Vec3 = tri.iter().map(|&i| glm::Vec3::from(data.vertices[i as usize].normal)).sum();
+                assert!(face.dot(&normals) > 0.0, "{} triangle {:?} winds clockwise", name, tri);
+                // every shape but the torus is convex around the origin
+                if name != "torus" && name != "plane" {
+                    assert!(face.dot(&(a + b + c)) > 0.0, "{} triangle {:?} faces inwards", name, tri);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn normals_and_tangents_are_unit_and_orthogonal() {
+        for (name, data) in shapes() {
+            for v in &data.vertices {
+                let n = glm::Vec3::from(v.normal);
+                let t = glm::vec3(v.tangent[0], v.tangent[1], v.tangent[2]);
+                assert!((n.norm() - 1.0).abs() < 1e-4, "{} normal {:?}", name, v.normal);
+                assert!((t.norm() - 1.0).abs() < 1e-4, "{} tangent {:?}", name, v.tangent);
+                assert!(n.dot(&t).abs() < 1e-4, "{} tangent {:?} not along the surface", name, v.tangent);
+                assert!(v.tangent[3] == 1.0 || v.tangent[3] == -1.0, "{} handedness {}", name, v.tangent[3]);
+            }
+        }
+    }
+
+    #[test]
+    fn icosphere_seam_copies_continue_past_one() {
+        let data = icosphere(1.0, 2);
+        let pole = |v: &&PrimitiveVertex| v.position[0].abs() < 1e-6 && v.position[2].abs() < 1e-6;
+        let copies: Vec<_> = data.vertices.iter().filter(|v| v.uv[0] > 1.0 && !pole(v)).collect();
+        assert!(!copies.is_empty());
+        for copy in copies {
+            // each copy is an earlier vertex a whole texture width further along
+            let original = data.vertices.iter().find(|v| v.position == copy.position && v.uv[0] <= 1.0).unwrap();
+            assert!((copy.uv[0] - original.uv[0] - 1.0).abs() < 1e-6);
+            assert_eq!(copy.uv[1], original.uv[1]);
+        }
+        for tri in data.indices.chunks_exact(3) {
+            let us = tri.iter().map(|&i| data.vertices[i as usize].uv[0]);
+            let (min, max) = us.fold((f32::MAX, f32::MIN), |(min, max), u| (min.min(u), max.max(u)));
+            assert!(max - min < 0.5, "triangle {:?} runs back across the seam", tri);
+        }
+    }
+
+    #[test]
+    fn degenerate_inputs_dont_panic() {
+        let shapes = [
+            plane(1.0, 1.0, 0, 0),
+            uv_sphere(1.0, 0, 0),
+            icosphere(1.0, 0),
+            cylinder(1.0, 1.0, 0),
+            cone(1.0, 1.0, 0),
+            torus(1.0, 0.5, 0, 0),
+            capsule(1.0, 1.0, 0, 0),
+            capsule(1.0, 0.0, 3, 1),
+            cube(0.0),
+            uv_sphere(0.0, 8, 4),
+        ];
+        for data in shapes {
+            assert_eq!(data.indices.len() % 3, 0);
+            assert!(data.indices.iter().all(|&i| (i as usize) < data.vertices.len()));
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use std::ffi::{c_void, CStr};
+use std::fs;
+use std::path::PathBuf;
+
+use beryllium::video::GlWindow;
+use gl33::{*, global_loader::*};
+
+use super::{has_extension, Error};
+use super::shader::{ProgramBuilder, ShaderProgram, StageSource};
+
+// GL_ARB_get_program_binary isn't part of GL 3.3, so gl33 doesn't have these.
+const GL_PROGRAM_BINARY_RETRIEVABLE_HINT: u32 = 0x8257;
+const GL_PROGRAM_BINARY_LENGTH: GLenum = GLenum(0x8741);
+const GL_NUM_PROGRAM_BINARY_FORMATS: GLenum = GLenum(0x87FE);
+
+type GetProgramBinaryFn = unsafe extern "system" fn(u32, i32, *mut i32, *mut u32, *mut c_void);
+type ProgramBinaryFn = unsafe extern "system" fn(u32, u32, *const c_void, i32);
+type ProgramParameteriFn = unsafe extern "system" fn(u32, u32, i32);
+
+struct BinaryFns {
+    get_program_binary: GetProgramBinaryFn,
+    program_binary: ProgramBinaryFn,
+    program_parameteri: ProgramParameteriFn,
+}
+
+/// Keeps linked program binaries on disk so the next run can skip compiling.
+///
+/// Binaries are keyed by a hash of every stage's (preprocessed) source and the
+/// driver's vendor, renderer and version strings, so a driver update just
+/// misses the cache. If `GL_ARB_get_program_binary` isn't available, or the
+/// driver rejects a stored binary, programs are compiled normally.
+pub struct ProgramCache {
+    dir: PathBuf,
+    driver: String,
+    fns: Option<BinaryFns>,
+}
+
+impl ProgramCache {
+    /// Sets up a cache that keeps its files in `dir`.
+    ///
+    /// Call this after [`load_gl`](crate::load_gl), it looks up the extension
+    /// functions through the window.
+    pub fn new(win: &GlWindow, dir: impl Into<PathBuf>) -> Self {
+        let driver = [GL_VENDOR, GL_RENDERER, GL_VERSION]
+            .iter()
+            .map(|&name| gl_string(name))
+            .collect::<Vec<_>>()
+            .join("\n");
+        // the format count is an unknown enum without the extension
+        let formats = if has_extension("GL_ARB_get_program_binary") {
+            let mut formats = 0;
+            unsafe { glGetIntegerv(GL_NUM_PROGRAM_BINARY_FORMATS, &mut formats) };
+            formats
+        } else {
+            0
+        };
+        let fns = if formats > 0 { unsafe { load_fns(win) } } else { None };
+        Self { dir: dir.into(), driver, fns }
+    }
+
+    /// Checks if binaries can actually be stored with this driver.
+    pub fn is_supported(&self) -> bool {
+        self.fns.is_some()
+    }
+
+    /// Loads the program out of the cache, or builds it and stores it.
+    pub fn build(&self, builder: &ProgramBuilder) -> Result<ShaderProgram, Error> {
+        let fns = match &self.fns {
+            Some(fns) => fns,
+            None => return builder.build(),
+        };
+        let path = self.dir.join(format!("{:016x}.bin", self.key(builder)));
+
+        if let Ok(bytes) = fs::read(&path) {
+            if let Some(p) = load_binary(fns, &bytes)? {
+                return Ok(p);
+            }
+            // Stale or rejected, it'll be replaced below.
+            let _ = fs::remove_file(&path);
+        }
+
+        let p = builder.build_with(|p| unsafe {
+            (fns.program_parameteri)(p.0, GL_PROGRAM_BINARY_RETRIEVABLE_HINT, 1)
+        })?;
+        if let Some(bytes) = store_binary(fns, &p) {
+            // A cache that can't be written is just a slower startup.
+            let _ = fs::create_dir_all(&self.dir).and_then(|_| fs::write(&path, bytes));
+        }
+        Ok(p)
+    }
+
+    fn key(&self, builder: &ProgramBuilder) -> u64 {
+        let mut hash = Fnv1a::new();
+        hash.write(self.driver.as_bytes());
+        for (ty, source) in builder.stages() {
+            hash.write(&(*ty as u32).to_le_bytes());
+            match source {
+                StageSource::Source(src) => hash.write(src.as_bytes()),
+                StageSource::Preprocessed(pre) => hash.write(pre.source.as_bytes()),
+            }
+        }
+        hash.finish()
+    }
+}
+
+unsafe fn load_fns(win: &GlWindow) -> Option<BinaryFns> {
+    let get_program_binary = win.get_proc_address(c"glGetProgramBinary".as_ptr().cast());
+    let program_binary = win.get_proc_address(c"glProgramBinary".as_ptr().cast());
+    let program_parameteri = win.get_proc_address(c"glProgramParameteri".as_ptr().cast());
+    if get_program_binary.is_null() || program_binary.is_null() || program_parameteri.is_null() {
+        return None;
+    }
+    Some(BinaryFns {
+        get_program_binary: std::mem::transmute::<*mut c_void, GetProgramBinaryFn>(get_program_binary),
+        program_binary: std::mem::transmute::<*mut c_void, ProgramBinaryFn>(program_binary),
+        program_parameteri: std::mem::transmute::<*mut c_void, ProgramParameteriFn>(program_parameteri),
+    })
+}
+
+/// Cache files are the binary format as a little endian `u32`, then the binary.
+fn load_binary(fns: &BinaryFns, bytes: &[u8]) -> Result<Option<ShaderProgram>, Error> {
+    if bytes.len() <= 4 {
+        return Ok(None);
+    }
+    let format = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    let binary = &bytes[4..];
+    let mut p = ShaderProgram::new()?;
+    unsafe { (fns.program_binary)(p.0, format, binary.as_ptr().cast(), binary.len() as i32) };
+    p.refresh_reflection();
+    if p.link_success() {
+        Ok(Some(p))
+    } else {
+        Ok(None)
+    }
+}
+
+fn store_binary(fns: &BinaryFns, p: &ShaderProgram) -> Option<Vec<u8>> {
+    let mut len = 0;
+    unsafe { glGetProgramiv(p.0, GL_PROGRAM_BINARY_LENGTH, &mut len) };
+    if len <= 0 {
+        return None;
+    }
+    let mut binary = vec![0_u8; len as usize];
+    let mut written = 0;
+    let mut format = 0;
+    unsafe {
+        (fns.get_program_binary)(p.0, len, &mut written, &mut format, binary.as_mut_ptr().cast());
+    }
+    if written <= 0 {
+        return None;
+    }
+    binary.truncate(written as usize);
+    let mut out = format.to_le_bytes().to_vec();
+    out.extend_from_slice(&binary);
+    Some(out)
+}
+
+fn gl_string(name: GLenum) -> String {
+    let ptr = unsafe { glGetString(name) };
+    if ptr.is_null() {
+        String::new()
+    } else {
+        unsafe { CStr::from_ptr(ptr.cast()) }.to_string_lossy().into_owned()
+    }
+}
+
+/// FNV-1a, so cache keys stay the same between builds of the program
+/// (`DefaultHasher` makes no such promise).
+struct Fnv1a(u64);
+impl Fnv1a {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= b as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
@@ -0,0 +1,93 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::Error;
+use super::preprocess::preprocess_file;
+use super::shader::{ShaderProgram, ShaderType};
+
+/// A [`ShaderProgram`] built from shader files that rebuilds itself when any
+/// of the files, or anything they `#include`, changes on disk.
+///
+/// Call [`ReloadableProgram::poll`](ReloadableProgram::poll) once a frame. If
+/// the new sources don't build the old program is kept and the error is
+/// printed, so a typo never takes the window down.
+pub struct ReloadableProgram {
+    stages: Vec<(ShaderType, PathBuf)>,
+    watched: Vec<(PathBuf, Option<SystemTime>)>,
+    program: ShaderProgram,
+}
+
+impl ReloadableProgram {
+    /// Builds the program from a vertex and a fragment file.
+    ///
+    /// The first build has nothing to fall back on, so its error is returned.
+    pub fn from_files(vert: impl AsRef<Path>, frag: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::from_stage_files(&[
+            (ShaderType::Vertex, vert.as_ref()),
+            (ShaderType::Fragment, frag.as_ref()),
+        ])
+    }
+
+    /// Builds the program from one file per stage, eg to add a geometry shader.
+    pub fn from_stage_files(stages: &[(ShaderType, &Path)]) -> Result<Self, Error> {
+        let stages: Vec<(ShaderType, PathBuf)> = stages
+            .iter()
+            .map(|(ty, path)| (*ty, path.to_path_buf()))
+            .collect();
+        let (program, files) = Self::build(&stages)?;
+        Ok(Self { stages, watched: stamp(files), program })
+    }
+
+    /// The most recent program that built successfully.
+    pub fn program(&self) -> &ShaderProgram {
+        &self.program
+    }
+
+    /// Checks the files' modification times and rebuilds if they changed.
+    ///
+    /// Returns `true` when the program was replaced, uniforms need to be set
+    /// again in that case since they live in the program object.
+    pub fn poll(&mut self) -> bool {
+        if self.watched.iter().all(|(path, time)| modified(path) == *time) {
+            return false;
+        }
+        match Self::build(&self.stages) {
+            Ok((program, files)) => {
+                self.program = program;
+                self.watched = stamp(files);
+                true
+            }
+            Err(e) => {
+                // Remember the new times anyway, otherwise a broken shader
+                // would be recompiled every frame until it's fixed.
+                let files = self.watched.drain(..).map(|(path, _)| path).collect();
+                self.watched = stamp(files);
+                eprintln!("Shader reload failed, keeping the previous program.\n{}", e);
+                false
+            }
+        }
+    }
+
+    fn build(stages: &[(ShaderType, PathBuf)]) -> Result<(ShaderProgram, Vec<PathBuf>), Error> {
+        let mut builder = ShaderProgram::builder();
+        let mut files = Vec::new();
+        for (ty, path) in stages {
+            let pre = preprocess_file(path, &[])?;
+            files.extend(pre.files.iter().map(|f| f.path.clone()));
+            builder = builder.preprocessed_stage(*ty, pre);
+        }
+        Ok((builder.build()?, files))
+    }
+}
+
+fn stamp(files: Vec<PathBuf>) -> Vec<(PathBuf, Option<SystemTime>)> {
+    files.into_iter().map(|path| {
+        let time = modified(&path);
+        (path, time)
+    }).collect()
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
@@ -0,0 +1,236 @@
+use gl33::{*, global_loader::*};
+
+use super::unpack_enum;
+use super::Error;
+
+/// What happens to texture coordinates outside of 0..1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wrap {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+    /// Samples [`TextureParams::border_color`] outside the texture.
+    ClampToBorder,
+}
+
+impl Wrap {
+    fn gl(self) -> GLenum {
+        match self {
+            Wrap::Repeat => GL_REPEAT,
+            Wrap::MirroredRepeat => GL_MIRRORED_REPEAT,
+            Wrap::ClampToEdge => GL_CLAMP_TO_EDGE,
+            Wrap::ClampToBorder => GL_CLAMP_TO_BORDER,
+        }
+    }
+}
+
+/// Filter for when the texture is drawn smaller than it is. The mipmap
+/// variants need a texture with mipmaps, otherwise it samples as black.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MinFilter {
+    Nearest,
+    Linear,
+    NearestMipmapNearest,
+    LinearMipmapNearest,
+    NearestMipmapLinear,
+    LinearMipmapLinear,
+}
+
+impl MinFilter {
+    fn gl(self) -> GLenum {
+        match self {
+            MinFilter::Nearest => GL_NEAREST,
+            MinFilter::Linear => GL_LINEAR,
+            MinFilter::NearestMipmapNearest => GL_NEAREST_MIPMAP_NEAREST,
+            MinFilter::LinearMipmapNearest => GL_LINEAR_MIPMAP_NEAREST,
+            MinFilter::NearestMipmapLinear => GL_NEAREST_MIPMAP_LINEAR,
+            MinFilter::LinearMipmapLinear => GL_LINEAR_MIPMAP_LINEAR,
+        }
+    }
+
+    /// Checks if this filter reads from the mip levels.
+    pub fn uses_mipmaps(self) -> bool {
+        !matches!(self, MinFilter::Nearest | MinFilter::Linear)
+    }
+}
+
+/// Filter for when the texture is drawn bigger than it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MagFilter {
+    Nearest,
+    Linear,
+}
+
+impl MagFilter {
+    fn gl(self) -> GLenum {
+        match self {
+            MagFilter::Nearest => GL_NEAREST,
+            MagFilter::Linear => GL_LINEAR,
+        }
+    }
+}
+
+/// How a texture gets sampled.
+///
+/// Apply it to a texture with
+/// [`Texture::set_params`](crate::texture::Texture::set_params), or to a
+/// [`Sampler`] to sample the same texture differently in different draws.
+///
+/// ```ignore
+/// let pixel_art = TextureParams::new()
+///     .wrap(Wrap::ClampToEdge)
+///     .min_filter(MinFilter::Nearest)
+///     .mag_filter(MagFilter::Nearest);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureParams {
+    pub wrap_s: Wrap,
+    pub wrap_t: Wrap,
+    pub wrap_r: Wrap,
+    pub min_filter: MinFilter,
+    pub mag_filter: MagFilter,
+    /// RGBA, only used by [`Wrap::ClampToBorder`].
+    pub border_color: [f32; 4],
+    pub lod_bias: f32,
+    pub min_lod: f32,
+    pub max_lod: f32,
+}
+
+impl Default for TextureParams {
+    /// Repeats on every axis with linear filtering, what `setParams` always
+    /// did. The LOD values are GL's defaults.
+    fn default() -> Self {
+        Self {
+            wrap_s: Wrap::Repeat,
+            wrap_t: Wrap::Repeat,
+            wrap_r: Wrap::Repeat,
+            min_filter: MinFilter::Linear,
+            mag_filter: MagFilter::Linear,
+            border_color: [0.0; 4],
+            lod_bias: 0.0,
+            min_lod: -1000.0,
+            max_lod: 1000.0,
+        }
+    }
+}
+
+impl TextureParams {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the wrap mode on every axis.
+    pub fn wrap(self, wrap: Wrap) -> Self {
+        Self { wrap_s: wrap, wrap_t: wrap, wrap_r: wrap, ..self }
+    }
+
+    pub fn wrap_s(self, wrap: Wrap) -> Self {
+        Self { wrap_s: wrap, ..self }
+    }
+
+    pub fn wrap_t(self, wrap: Wrap) -> Self {
+        Self { wrap_t: wrap, ..self }
+    }
+
+    pub fn wrap_r(self, wrap: Wrap) -> Self {
+        Self { wrap_r: wrap, ..self }
+    }
+
+    pub fn min_filter(self, filter: MinFilter) -> Self {
+        Self { min_filter: filter, ..self }
+    }
+
+    pub fn mag_filter(self, filter: MagFilter) -> Self {
+        Self { mag_filter: filter, ..self }
+    }
+
+    /// Clamps to the border on every axis and sets the border color.
+    pub fn border(self, color: [f32; 4]) -> Self {
+        Self { border_color: color, ..self.wrap(Wrap::ClampToBorder) }
+    }
+
+    /// Added to the mip level the GPU picks. Negative is sharper.
+    pub fn lod_bias(self, bias: f32) -> Self {
+        Self { lod_bias: bias, ..self }
+    }
+
+    /// Limits which mip levels get sampled.
+    pub fn lod_range(self, min: f32, max: f32) -> Self {
+        Self { min_lod: min, max_lod: max, ..self }
+    }
+
+    /// Sets every parameter on the texture bound to `target`.
+    pub(crate) fn apply(&self, target: GLenum) {
+        unsafe {
+            self.apply_with(
+                |name, value| glTexParameteri(target, name, value),
+                |name, value| glTexParameterf(target, name, value),
+                |name, values| glTexParameterfv(target, name, values.as_ptr()),
+            );
+        }
+    }
+
+    fn apply_with(&self, int: impl Fn(GLenum, i32), float: impl Fn(GLenum, f32), floats: impl Fn(GLenum, &[f32; 4])) {
+        int(GL_TEXTURE_WRAP_S, unpack_enum(self.wrap_s.gl()));
+        int(GL_TEXTURE_WRAP_T, unpack_enum(self.wrap_t.gl()));
+        int(GL_TEXTURE_WRAP_R, unpack_enum(self.wrap_r.gl()));
+        int(GL_TEXTURE_MIN_FILTER, unpack_enum(self.min_filter.gl()));
+        int(GL_TEXTURE_MAG_FILTER, unpack_enum(self.mag_filter.gl()));
+        floats(GL_TEXTURE_BORDER_COLOR, &self.border_color);
+        float(GL_TEXTURE_LOD_BIAS, self.lod_bias);
+        float(GL_TEXTURE_MIN_LOD, self.min_lod);
+        float(GL_TEXTURE_MAX_LOD, self.max_lod);
+    }
+}
+
+/// A [Sampler Object](https://www.khronos.org/opengl/wiki/Sampler_Object).
+///
+/// While it's bound to a texture unit its parameters are used instead of the
+/// parameters of the texture on that unit.
+pub struct Sampler(pub u32);
+impl Sampler {
+    pub fn new() -> Result<Self, Error> {
+        let mut sampler = 0;
+        unsafe { glGenSamplers(1, &mut sampler) };
+        if sampler != 0 {
+            Ok(Self(sampler))
+        } else {
+            Err(Error::Alloc("sampler"))
+        }
+    }
+
+    /// Makes a sampler and sets its parameters.
+    pub fn with_params(params: &TextureParams) -> Result<Self, Error> {
+        let sampler = Self::new()?;
+        sampler.set_params(params);
+        Ok(sampler)
+    }
+
+    pub fn set_params(&self, params: &TextureParams) {
+        let id = self.0;
+        unsafe {
+            params.apply_with(
+                |name, value| glSamplerParameteri(id, name, value),
+                |name, value| glSamplerParameterf(id, name, value),
+                |name, values| glSamplerParameterfv(id, name, values.as_ptr()),
+            );
+        }
+    }
+
+    /// Binds the sampler to a texture unit, `GL_TEXTURE0` and up like
+    /// [`Texture::bind`](crate::texture::Texture::bind).
+    pub fn bind(&self, tex_unit: GLenum) {
+        unsafe { glBindSampler(tex_unit.0 - GL_TEXTURE0.0, self.0) };
+    }
+
+    /// Goes back to the texture's own parameters on a texture unit.
+    pub fn clear_binding(tex_unit: GLenum) {
+        unsafe { glBindSampler(tex_unit.0 - GL_TEXTURE0.0, 0) };
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        unsafe { glDeleteSamplers(1, &self.0) };
+    }
+}
@@ -1,10 +1,16 @@
+use std::collections::HashMap;
 use std::ffi::CString;
 use gl33::{*, global_loader::*};
 use nalgebra_glm as glm;
 
 use super::unpack_enum as unpack_enum;
+use super::Error;
+use super::diagnostic::{self, Diagnostic};
+use super::preprocess::{Preprocessed, SourceFile};
+use super::uniform::{self, UniformValue};
 
 /// The types of shader object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u32)]
 pub enum ShaderType {
     /// Vertex shaders determine the position of geometry within the screen.
@@ -13,11 +19,17 @@ pub enum ShaderType {
     ///
     /// Also other values, but mostly color.
     Fragment = GL_FRAGMENT_SHADER.0,
+    /// Geometry shaders run once per primitive, after the vertex shader, and
+    /// can emit any number of new primitives.
+    Geometry = GL_GEOMETRY_SHADER.0,
   }
 
 /// A handle to a [Shader
 /// Object](https://www.khronos.org/opengl/wiki/GLSL_Object#Shader_objects)
-pub struct Shader(pub u32);
+///
+/// Also keeps a copy of the last source string, and the files it was
+/// preprocessed from, so compile errors can point back into them.
+pub struct Shader(pub u32, String, Vec<SourceFile>);
 impl Shader {
     /// Makes a new shader.
     ///
@@ -25,24 +37,38 @@ impl Shader {
     ///
     /// Possibly skip the direct creation of the shader object and use
     /// [`ShaderProgram::from_vert_frag`](ShaderProgram::from_vert_frag).
-    pub fn new(ty: ShaderType) -> Option<Self> {
+    pub fn new(ty: ShaderType) -> Result<Self, Error> {
         let shader =  glCreateShader(GLenum(ty as u32));
         if shader != 0 {
-            Some(Self(shader))
+            Ok(Self(shader, String::new(), Vec::new()))
         } else {
-            None
+            Err(Error::Alloc("shader"))
         }
     }
 
     /// Assigns a source string to the shader
     /// 
     /// Replaces any previously assigned shaders
-    pub fn set_source(&self, src: String) {
+    pub fn set_source(&mut self, src: String) {
         unsafe{
             glShaderSource(self.0, 1, 
                                 &(src.as_bytes().as_ptr().cast()), 
                                 &(src.len().try_into().unwrap()))
         };
+        self.1 = src;
+        self.2.clear();
+    }
+
+    /// Assigns the output of the [preprocessor](crate::preprocess) to the
+    /// shader, diagnostics will then name the included file they came from.
+    pub fn set_preprocessed(&mut self, pre: &Preprocessed) {
+        self.set_source(pre.source.clone());
+        self.2 = pre.files.clone();
+    }
+
+    /// The source string last passed to [`Shader::set_source`](Shader::set_source).
+    pub fn source(&self) -> &str {
+        &self.1
     }
 
     /// Compiles the shader based on the current source
@@ -77,6 +103,22 @@ impl Shader {
         String::from_utf8_lossy(&v).into_owned()
     }
 
+    /// Parses the info log into diagnostics, with excerpts taken from the
+    /// current source.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let mut out = diagnostic::parse_info_log(&self.info_log());
+        for d in out.iter_mut() {
+            match d.file.and_then(|i| self.2.get(i as usize)) {
+                Some(file) => {
+                    d.path = Some(file.path.clone());
+                    d.excerpt = d.render_excerpt(&file.text);
+                }
+                None => d.excerpt = d.render_excerpt(&self.1),
+            }
+        }
+        out
+    }
+
     /// Marks a shader for deletion.
     ///
     /// Note: This _does not_ immediately delete the shader. It only marks it for
@@ -87,22 +129,35 @@ impl Shader {
     }
 
     /// Takes a shader type and source string and produces either the compiled
-    /// shader or an error message.
+    /// shader or a compile error holding the info log.
     ///
     /// Prefer [`ShaderProgram::from_vert_frag`](ShaderProgram::from_vert_frag),
     /// it makes a complete program from the vertex and fragment sources all at
     /// once.
-    pub fn from_source(ty: ShaderType, source: String) -> Result<Self, String> {
-        let id = Self::new(ty)
-                .ok_or_else(|| "Couldn't allocate new shader".to_string())?;
+    pub fn from_source(ty: ShaderType, source: String) -> Result<Self, Error> {
+        let mut id = Self::new(ty)?;
         id.set_source(source);
+        id.compile_checked(ty)
+    }
+
+    /// Same as [`Shader::from_source`](Shader::from_source) but for
+    /// [preprocessed](crate::preprocess) source.
+    pub fn from_preprocessed(ty: ShaderType, pre: &Preprocessed) -> Result<Self, Error> {
+        let mut id = Self::new(ty)?;
+        id.set_preprocessed(pre);
+        id.compile_checked(ty)
+    }
+
+    fn compile_checked(self, ty: ShaderType) -> Result<Self, Error> {
+        let id = self;
         id.compile();
         if id.compile_success() {
             Ok(id)
         } else {
-            let out = id.info_log();
+            let log = id.info_log();
+            let diagnostics = id.diagnostics();
             id.mark_delete();
-            Err(out)
+            Err(Error::Compile { stage: ty, log, diagnostics })
         }
     }
 }
@@ -113,21 +168,115 @@ impl Drop for Shader {
     }
 }
 
+/// What GL reports about one active uniform or vertex attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActiveVariable {
+    /// The location to pass to `glUniform*` or `glVertexAttribPointer`.
+    ///
+    /// This is `-1` for uniforms that live in a uniform block.
+    pub location: i32,
+    /// The GLSL type, eg `GL_FLOAT_VEC3` or `GL_SAMPLER_2D`.
+    pub ty: GLenum,
+    /// The array length, `1` for things that aren't arrays.
+    pub size: i32,
+}
+
+/// The active uniforms and attributes of a linked program, by name.
+///
+/// Arrays are listed under their plain name (`lights`) and under every
+/// element (`lights[0]`, `lights[1]`, ...), the element entries have `size`
+/// counting from that element to the end of the array.
+#[derive(Debug, Clone, Default)]
+pub struct Reflection {
+    pub uniforms: HashMap<String, ActiveVariable>,
+    pub attributes: HashMap<String, ActiveVariable>,
+}
+
+impl Reflection {
+    /// Asks GL for everything that's active in a linked program.
+    pub fn query(program: u32) -> Self {
+        let mut out = Self::default();
+        let mut count = 0;
+        let mut max_len = 0;
+        unsafe {
+            glGetProgramiv(program, GL_ACTIVE_UNIFORMS, &mut count);
+            glGetProgramiv(program, GL_ACTIVE_UNIFORM_MAX_LENGTH, &mut max_len);
+        }
+        for i in 0..count.max(0) as u32 {
+            let (name, size, ty) = read_active(max_len, |buf_len, len, size, ty, name| unsafe {
+                glGetActiveUniform(program, i, buf_len, len, size, ty, name)
+            });
+            insert_active(&mut out.uniforms, &name, size, ty, |n| unsafe {
+                glGetUniformLocation(program, n.as_ptr() as *const u8)
+            });
+        }
+
+        unsafe {
+            glGetProgramiv(program, GL_ACTIVE_ATTRIBUTES, &mut count);
+            glGetProgramiv(program, GL_ACTIVE_ATTRIBUTE_MAX_LENGTH, &mut max_len);
+        }
+        for i in 0..count.max(0) as u32 {
+            let (name, size, ty) = read_active(max_len, |buf_len, len, size, ty, name| unsafe {
+                glGetActiveAttrib(program, i, buf_len, len, size, ty, name)
+            });
+            insert_active(&mut out.attributes, &name, size, ty, |n| unsafe {
+                glGetAttribLocation(program, n.as_ptr() as *const u8)
+            });
+        }
+        out
+    }
+}
+
+/// Calls one of the `glGetActive*` functions and collects what it wrote.
+fn read_active(max_len: i32, get: impl Fn(i32, *mut i32, *mut i32, *mut GLenum, *mut u8)) -> (String, i32, GLenum) {
+    let mut v: Vec<u8> = Vec::with_capacity(max_len.max(1).try_into().unwrap());
+    let mut len_written = 0_i32;
+    let mut size = 0;
+    let mut ty = GLenum(0);
+    get(v.capacity().try_into().unwrap(), &mut len_written, &mut size, &mut ty, v.as_mut_ptr());
+    unsafe { v.set_len(len_written.try_into().unwrap()) };
+    (String::from_utf8_lossy(&v).into_owned(), size, ty)
+}
+
+fn insert_active(
+    map: &mut HashMap<String, ActiveVariable>,
+    name: &str,
+    size: i32,
+    ty: GLenum,
+    location_of: impl Fn(&CString) -> i32,
+) {
+    let base = name.strip_suffix("[0]").unwrap_or(name);
+    let c_name = CString::new(base).expect("GL names don't have nul bytes");
+    map.insert(base.to_string(), ActiveVariable { location: location_of(&c_name), ty, size });
+    if base.len() != name.len() {
+        for element in 0..size {
+            let element_name = format!("{}[{}]", base, element);
+            let c_name = CString::new(element_name.as_str()).expect("GL names don't have nul bytes");
+            let location = location_of(&c_name);
+            map.insert(element_name, ActiveVariable { location, ty, size: size - element });
+        }
+    }
+}
+
 /// A handle to a [Program
 /// Object](https://www.khronos.org/opengl/wiki/GLSL_Object#Program_objects)
-pub struct ShaderProgram(pub u32);
+///
+/// After a successful link the program's active uniforms and attributes are
+/// looked up once and kept in a [`Reflection`], so setting a uniform is just a
+/// hash lookup.
+pub struct ShaderProgram(pub u32, Reflection);
 impl ShaderProgram {
     /// Allocates a new program object.
     ///
     /// Prefer [`ShaderProgram::from_vert_frag`](ShaderProgram::from_vert_frag),
     /// it makes a complete program from the vertex and fragment sources all at
     /// once.
-    pub fn new() -> Option<Self> {
+    pub fn new() -> Result<Self, Error> {
         let id = glCreateProgram();
         if id != 0 {
-            Some(Self(id))
+            Ok(Self(id, Reflection::default()))
         } else {
-            None
+            Err(Error::Alloc("program"))
         }
     }
 
@@ -137,8 +286,36 @@ impl ShaderProgram {
     }
 
     /// Links the various attached, compiled shader objects into a usable program.
-    pub fn link_program(&self) {
+    ///
+    /// When the link works the active uniforms and attributes are queried.
+    pub fn link_program(&mut self) {
         glLinkProgram(self.0);
+        self.refresh_reflection();
+    }
+
+    /// Re-reads the active uniforms and attributes, for when the program was
+    /// linked some other way, like from a program binary.
+    pub(crate) fn refresh_reflection(&mut self) {
+        self.1 = if self.link_success() {
+            Reflection::query(self.0)
+        } else {
+            Reflection::default()
+        };
+    }
+
+    /// The active uniforms and attributes found by the last successful link.
+    pub fn reflection(&self) -> &Reflection {
+        &self.1
+    }
+
+    /// Info about a single active uniform.
+    pub fn uniform(&self, uniform_name: &str) -> Option<&ActiveVariable> {
+        self.1.uniforms.get(uniform_name)
+    }
+
+    /// Info about a single active vertex attribute.
+    pub fn attribute(&self, attribute_name: &str) -> Option<&ActiveVariable> {
+        self.1.attributes.get(attribute_name)
     }
 
     /// Checks if the last linking operation was successful.
@@ -183,59 +360,104 @@ impl ShaderProgram {
     }
 
     /// Takes a vertex shader source string and a fragment shader source string
-    /// and either gets you a working program object or gets you an error message.
+    /// and either gets you a working program object or gets you an error.
     ///
     /// This is the preferred way to create a simple shader program in the common
     /// case. It's just less error prone than doing all the steps yourself.
-    pub fn from_vert_frag(vert: String, frag: String) -> Result<Self, String> {
-        let p =
-            Self::new().ok_or_else(|| "Couldn't allocate a program".to_string())?;
-        let v = Shader::from_source(ShaderType::Vertex, vert)
-            .map_err(|e| format!("Vertex Compile Error: {}", e))?;
-        let f = Shader::from_source(ShaderType::Fragment, frag)
-            .map_err(|e| format!("Fragment Compile Error: {}", e))?;
-        p.attach_shader(&v);
-        p.attach_shader(&f);
-        p.link_program();
-        v.mark_delete();
-        f.mark_delete();
-        if p.link_success() {
-            Ok(p)
-        } else {
-            let out = format!("Program Link Error: {}", p.info_log());
-            p.delete();
-            Err(out)
-        }
+    pub fn from_vert_frag(vert: String, frag: String) -> Result<Self, Error> {
+        Self::builder()
+            .stage(ShaderType::Vertex, vert)
+            .stage(ShaderType::Fragment, frag)
+            .build()
     }
 
+    /// Same as [`ShaderProgram::from_vert_frag`](ShaderProgram::from_vert_frag)
+    /// but for [preprocessed](crate::preprocess) sources.
+    pub fn from_preprocessed(vert: &Preprocessed, frag: &Preprocessed) -> Result<Self, Error> {
+        Self::builder()
+            .preprocessed_stage(ShaderType::Vertex, vert.clone())
+            .preprocessed_stage(ShaderType::Fragment, frag.clone())
+            .build()
+    }
 
-    ///Create functions for uniforms
-    pub fn setBool(&self, uniform_name: &str, vals: Vec<bool>) -> bool {
-        unsafe {
-        let id = glGetUniformLocation(self.0, CString::new(uniform_name).expect("").as_ptr() as *const u8);
-        assert_ne!(id, -1, "Uniform not found: {}", uniform_name);
-    
-        Self::uniformi_helper(id, vals.iter().map(|&x|x as i32).collect())
+    /// Starts a [`ProgramBuilder`] for programs with stages other than just
+    /// vertex and fragment, like a geometry shader.
+    pub fn builder() -> ProgramBuilder {
+        ProgramBuilder::default()
+    }
+
+
+    /// Looks up the location of a uniform, erroring if the program doesn't have it.
+    pub fn uniform_location(&self, uniform_name: &str) -> Result<i32, Error> {
+        match self.uniform(uniform_name) {
+            Some(u) if u.location != -1 => Ok(u.location),
+            _ => Err(Error::MissingUniform(uniform_name.to_string())),
         }
     }
 
-    pub fn setInt(&self, uniform_name: &str, vals: Vec<i32>) -> bool {
-        unsafe {
-        let id = glGetUniformLocation(self.0, CString::new(uniform_name).expect("").as_ptr() as *const u8);
-        assert_ne!(id, -1, "Uniform not found: {}", uniform_name);
-       
-        Self::uniformi_helper(id, vals)
+    /// Sets a uniform of any type that implements [`Uniform`](crate::uniform::Uniform),
+    /// or a slice of them for arrays.
+    ///
+    /// Errors if the uniform isn't active, the value's type doesn't match the
+    /// uniform's type, or more elements are given than the array has. The
+    /// program needs to be the one in use.
+    pub fn set_uniform<V: UniformValue + ?Sized>(&self, uniform_name: &str, value: &V) -> Result<(), Error> {
+        let u = match self.uniform(uniform_name) {
+            Some(u) if u.location != -1 => u,
+            _ => return Err(Error::MissingUniform(uniform_name.to_string())),
+        };
+        if !uniform::types_compatible(value.gl_type(), u.ty) {
+            return Err(Error::UniformType {
+                name: uniform_name.to_string(),
+                expected: u.ty,
+                found: value.gl_type(),
+            });
         }
+        if value.count() > u.size.max(0) as usize {
+            return Err(Error::UniformSize {
+                name: uniform_name.to_string(),
+                len: value.count(),
+                size: u.size,
+            });
+        }
+        value.upload(u.location);
+        Ok(())
+    }
+
+    /// Points the named uniform block at a uniform buffer binding point, see
+    /// [`UniformBuffer`](crate::uniform_buffer::UniformBuffer).
+    pub fn bind_uniform_block(&self, block_name: &str, binding: u32) -> Result<(), Error> {
+        let c_name = CString::new(block_name)
+            .map_err(|_| Error::MissingUniformBlock(block_name.to_string()))?;
+        let index = unsafe { glGetUniformBlockIndex(self.0, c_name.as_ptr() as *const u8) };
+        if index == GL_INVALID_INDEX {
+            return Err(Error::MissingUniformBlock(block_name.to_string()));
+        }
+        unsafe { glUniformBlockBinding(self.0, index, binding) };
+        Ok(())
+    }
+
+    ///Create functions for uniforms
+    ///
+    ///The `bool` is `false` when the number of values isn't in 1..=4.
+    ///Prefer [`ShaderProgram::set_uniform`](ShaderProgram::set_uniform), it
+    ///checks the types.
+    pub fn setBool(&self, uniform_name: &str, vals: Vec<bool>) -> Result<bool, Error> {
+        let id = self.uniform_location(uniform_name)?;
+        Ok(Self::uniformi_helper(id, vals.iter().map(|&x|x as i32).collect()))
+    }
+
+    pub fn setInt(&self, uniform_name: &str, vals: Vec<i32>) -> Result<bool, Error> {
+        let id = self.uniform_location(uniform_name)?;
+        Ok(Self::uniformi_helper(id, vals))
     }
 
 
-    pub fn setFloat(&self, uniform_name: &str, vals: Vec<f32>) -> bool {
+    pub fn setFloat(&self, uniform_name: &str, vals: Vec<f32>) -> Result<bool, Error> {
+        let id = self.uniform_location(uniform_name)?;
         unsafe {
-        let id = glGetUniformLocation(self.0, CString::new(uniform_name).expect("").as_ptr() as *const u8);
-        assert_ne!(id, -1, "Uniform not found: {}", uniform_name);
-   
         let mut iter = vals.clone().into_iter();
-        match vals.len() {
+        Ok(match vals.len() {
             1 => {glUniform1f(id, iter.next().expect("Bad input")); true},
             2 => {glUniform2f(id, iter.next().expect("Bad input"),
                                              iter.next().expect("Bad input"),); true},
@@ -247,7 +469,7 @@ impl ShaderProgram {
                                             iter.next().expect("Bad input"),
                                             iter.next().expect("Bad input"),); true},
             _ => false
-        }
+        })
         }
     }
 
@@ -270,16 +492,90 @@ impl ShaderProgram {
         }
     }
 
-    pub fn setMat4(&self, uniform_name: &str, vec_matrix: Vec<glm::Mat4>)  { //add bool
+    pub fn setMat4(&self, uniform_name: &str, vec_matrix: Vec<glm::Mat4>) -> Result<(), Error> {
         let vals: Vec<f32> = vec_matrix.iter().flatten().copied().collect();
+        let id = self.uniform_location(uniform_name)?;
         unsafe{
-        let id = glGetUniformLocation(self.0, CString::new(uniform_name).expect("").as_ptr() as *const u8);
-        assert_ne!(id, -1, "Uniform not found: {}", uniform_name);
         glUniformMatrix4fv(id, vec_matrix.len() as i32, unpack_enum(GL_FALSE) as u8, vals.as_ptr());
         }
+        Ok(())
     }
 }
 
+/// The source of one stage in a [`ProgramBuilder`].
+#[derive(Debug, Clone)]
+pub enum StageSource {
+    Source(String),
+    Preprocessed(Preprocessed),
+}
+
+/// Collects any set of shader stages and links them into a [`ShaderProgram`].
+///
+/// ```ignore
+/// let program = ShaderProgram::builder()
+///     .stage(ShaderType::Vertex, vert)
+///     .stage(ShaderType::Geometry, geom)
+///     .stage(ShaderType::Fragment, frag)
+///     .build()?;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ProgramBuilder {
+    stages: Vec<(ShaderType, StageSource)>,
+}
+
+impl ProgramBuilder {
+    /// Adds a stage from a plain source string.
+    pub fn stage(mut self, ty: ShaderType, source: String) -> Self {
+        self.stages.push((ty, StageSource::Source(source)));
+        self
+    }
+
+    /// Adds a stage from [preprocessed](crate::preprocess) source.
+    pub fn preprocessed_stage(mut self, ty: ShaderType, pre: Preprocessed) -> Self {
+        self.stages.push((ty, StageSource::Preprocessed(pre)));
+        self
+    }
+
+    /// The stages added so far, in order.
+    pub fn stages(&self) -> &[(ShaderType, StageSource)] {
+        &self.stages
+    }
+
+    /// Compiles every stage, attaches them all and links.
+    ///
+    /// A compile failure is reported as an [`Error::Compile`] naming the stage
+    /// that failed, a link failure as an [`Error::Link`].
+    pub fn build(&self) -> Result<ShaderProgram, Error> {
+        self.build_with(|_| ())
+    }
+
+    /// Same as [`ProgramBuilder::build`](ProgramBuilder::build), but calls
+    /// `before_link` once the stages are attached, to set program parameters.
+    pub fn build_with(&self, before_link: impl FnOnce(&ShaderProgram)) -> Result<ShaderProgram, Error> {
+        let mut p = ShaderProgram::new()?;
+        let mut shaders = Vec::with_capacity(self.stages.len());
+        for (ty, source) in &self.stages {
+            let shader = match source {
+                StageSource::Source(src) => Shader::from_source(*ty, src.clone())?,
+                StageSource::Preprocessed(pre) => Shader::from_preprocessed(*ty, pre)?,
+            };
+            p.attach_shader(&shader);
+            shaders.push(shader);
+        }
+        before_link(&p);
+        p.link_program();
+        for shader in shaders {
+            shader.mark_delete();
+        }
+        if p.link_success() {
+            Ok(p)
+        } else {
+            let log = p.info_log();
+            p.delete();
+            Err(Error::Link(log))
+        }
+    }
+}
     
 impl Drop for ShaderProgram {
     fn drop(&mut self) {
@@ -0,0 +1,107 @@
+use gl33::{*, global_loader::*};
+use nalgebra_glm as glm;
+
+use super::{BufferType, Error, VertexArray};
+use super::buffer::{Buffer, Usage};
+use super::cubemap::CubeMap;
+use super::shader::ShaderProgram;
+use super::texture_unit::TextureUnits;
+use super::vertex::{VertexAttribute, VertexLayout};
+
+const VERTEX_SHADER: &str = r#"#version 330 core
+layout (location = 0) in vec3 aPos;
+
+out vec3 TexCoord;
+
+uniform mat4 view;
+uniform mat4 projection;
+
+void main() {
+    TexCoord = aPos;
+    vec4 pos = projection * view * vec4(aPos, 1.0);
+    // z = w puts the sky on the far plane after the perspective divide
+    gl_Position = pos.xyww;
+}
+"#;
+
+const FRAGMENT_SHADER: &str = r#"#version 330 core
+in vec3 TexCoord;
+
+out vec4 FragColor;
+
+uniform samplerCube skybox;
+
+void main() {
+    FragColor = texture(skybox, TexCoord);
+}
+"#;
+
+#[rustfmt::skip]
+const VERTICES: [f32; 36 * 3] = [
+    -1.0,  1.0, -1.0,  -1.0, -1.0, -1.0,   1.0, -1.0, -1.0,
+     1.0, -1.0, -1.0,   1.0,  1.0, -1.0,  -1.0,  1.0, -1.0,
+
+    -1.0, -1.0,  1.0,  -1.0, -1.0, -1.0,  -1.0,  1.0, -1.0,
+    -1.0,  1.0, -1.0,  -1.0,  1.0,  1.0,  -1.0, -1.0,  1.0,
+
+     1.0, -1.0, -1.0,   1.0, -1.0,  1.0,   1.0,  1.0,  1.0,
+     1.0,  1.0,  1.0,   1.0,  1.0, -1.0,   1.0, -1.0, -1.0,
+
+    -1.0, -1.0,  1.0,  -1.0,  1.0,  1.0,   1.0,  1.0,  1.0,
+     1.0,  1.0,  1.0,   1.0, -1.0,  1.0,  -1.0, -1.0,  1.0,
+
+    -1.0,  1.0, -1.0,   1.0,  1.0, -1.0,   1.0,  1.0,  1.0,
+     1.0,  1.0,  1.0,  -1.0,  1.0,  1.0,  -1.0,  1.0, -1.0,
+
+    -1.0, -1.0, -1.0,  -1.0, -1.0,  1.0,   1.0, -1.0, -1.0,
+     1.0, -1.0, -1.0,  -1.0, -1.0,  1.0,   1.0, -1.0,  1.0,
+];
+
+/// Draws a [`CubeMap`] around the camera.
+///
+/// The sky is drawn at the far plane with `GL_LEQUAL`, so draw it after the
+/// rest of the scene and it only fills the pixels nothing else covered.
+pub struct Skybox {
+    vao: VertexArray,
+    _vbo: Buffer<f32>,
+    program: ShaderProgram,
+}
+
+impl Skybox {
+    pub fn new() -> Result<Self, Error> {
+        let program = ShaderProgram::from_vert_frag(VERTEX_SHADER.to_string(), FRAGMENT_SHADER.to_string())?;
+
+        let vao = VertexArray::new()?;
+        vao.bind();
+        let vbo = Buffer::from_slice(BufferType::Array, &VERTICES, Usage::StaticDraw)?;
+        vao.set_layout(vbo.buffer(), &VertexLayout::new(3 * size_of::<f32>()).attribute(VertexAttribute::float(0, 3, 0)));
+        VertexArray::clear_bind();
+
+        Ok(Self { vao, _vbo: vbo, program })
+    }
+
+    /// Draws the sky, binding `cube_map` to a unit from `units`.
+    ///
+    /// Only the rotation of `view` is used, the sky stays put as the camera
+    /// moves. Afterwards the sky's program is in use and no VAO is bound, so
+    /// bind your own again before the next draw. The depth function is put
+    /// back the way it was.
+    pub fn draw(&self, units: &mut TextureUnits, cube_map: &CubeMap, view: &glm::Mat4, projection: &glm::Mat4) -> Result<(), Error> {
+        let rotation = glm::mat3_to_mat4(&glm::mat4_to_mat3(view));
+        self.program.use_program();
+        self.program.set_uniform("view", &rotation)?;
+        self.program.set_uniform("projection", projection)?;
+        units.bind(&self.program, "skybox", cube_map)?;
+
+        self.vao.bind();
+        let mut depth_func = 0;
+        unsafe {
+            glGetIntegerv(GL_DEPTH_FUNC, &mut depth_func);
+            glDepthFunc(GL_LEQUAL);
+            glDrawArrays(GL_TRIANGLES, 0, 36);
+            glDepthFunc(GLenum(depth_func as u32));
+        }
+        VertexArray::clear_bind();
+        Ok(())
+    }
+}
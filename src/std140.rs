@@ -0,0 +1,198 @@
+//! Types for building Rust structs that match a GLSL `layout(std140)` block.
+//!
+//! std140 aligns `vec3`, `vec4` and matrix columns to 16 bytes, which Rust's
+//! `repr(C)` doesn't know about. Instead of guessing, structs made with
+//! [`std140_struct!`](crate::std140_struct) spell out every padding field and
+//! the macro checks at compile time that each field sits at an offset std140
+//! agrees with.
+//!
+//! ```ignore
+//! std140_struct! {
+//!     pub struct Light {
+//!         pub position: std140::Vec3,
+//!         pub strength: f32,
+//!         pub color: std140::Vec3,
+//!         pub _pad: std140::Padding<1>,
+//!     }
+//! }
+//! ```
+
+use bytemuck::{Pod, Zeroable};
+use nalgebra_glm as glm;
+
+/// A type that can be a field of a [`Std140`] struct.
+///
+/// # Safety
+/// `ALIGN` must be the std140 base alignment of the GLSL type this matches and
+/// the type must have the same size as the GLSL type.
+pub unsafe trait Std140Field: Pod {
+    const ALIGN: usize;
+}
+
+/// A struct whose memory layout matches a GLSL `layout(std140)` block.
+///
+/// # Safety
+/// Use [`std140_struct!`](crate::std140_struct), it checks the layout.
+pub unsafe trait Std140: Pod {}
+
+/// Types that can be the element of a std140 array. Array strides are rounded
+/// up to 16 bytes in std140, so only 16 byte multiples line up with Rust.
+///
+/// # Safety
+/// The size must be a multiple of 16.
+pub unsafe trait Std140ArrayElement: Std140Field {}
+
+unsafe impl Std140Field for f32 {
+    const ALIGN: usize = 4;
+}
+unsafe impl Std140Field for i32 {
+    const ALIGN: usize = 4;
+}
+unsafe impl Std140Field for u32 {
+    const ALIGN: usize = 4;
+}
+
+/// Explicit padding, `N` is counted in 4 byte words.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Padding<const N: usize>(pub [u32; N]);
+impl<const N: usize> Default for Padding<N> {
+    fn default() -> Self {
+        Self([0; N])
+    }
+}
+unsafe impl<const N: usize> Zeroable for Padding<N> {}
+unsafe impl<const N: usize> Pod for Padding<N> {}
+unsafe impl<const N: usize> Std140Field for Padding<N> {
+    const ALIGN: usize = 4;
+}
+
+macro_rules! std140_type {
+    ($(#[$meta:meta])* $name:ident, $inner:ty, $align:expr) => {
+        $(#[$meta])*
+        #[repr(C)]
+        #[derive(Debug, Clone, Copy, Default, PartialEq)]
+        pub struct $name(pub $inner);
+        unsafe impl Zeroable for $name {}
+        unsafe impl Pod for $name {}
+        unsafe impl Std140Field for $name {
+            const ALIGN: usize = $align;
+        }
+    };
+}
+
+std140_type!(
+    /// A GLSL `vec2`.
+    Vec2, [f32; 2], 8);
+std140_type!(
+    /// A GLSL `vec3`. It's 16 byte aligned but only 12 bytes long, so a
+    /// scalar can go right after it.
+    Vec3, [f32; 3], 16);
+std140_type!(
+    /// A GLSL `vec4`.
+    Vec4, [f32; 4], 16);
+std140_type!(
+    /// A GLSL `ivec4`.
+    IVec4, [i32; 4], 16);
+std140_type!(
+    /// A GLSL `uvec4`.
+    UVec4, [u32; 4], 16);
+std140_type!(
+    /// A GLSL `mat3`, every column is padded out to a `vec4`.
+    Mat3, [[f32; 4]; 3], 16);
+std140_type!(
+    /// A GLSL `mat4`.
+    Mat4, [[f32; 4]; 4], 16);
+
+unsafe impl Std140ArrayElement for Vec4 {}
+unsafe impl Std140ArrayElement for IVec4 {}
+unsafe impl Std140ArrayElement for UVec4 {}
+unsafe impl Std140ArrayElement for Mat3 {}
+unsafe impl Std140ArrayElement for Mat4 {}
+
+unsafe impl<T: Std140ArrayElement, const N: usize> Std140Field for [T; N] {
+    const ALIGN: usize = 16;
+}
+
+impl From<glm::Vec2> for Vec2 {
+    fn from(v: glm::Vec2) -> Self {
+        Self([v.x, v.y])
+    }
+}
+impl From<glm::Vec3> for Vec3 {
+    fn from(v: glm::Vec3) -> Self {
+        Self([v.x, v.y, v.z])
+    }
+}
+impl From<glm::Vec4> for Vec4 {
+    fn from(v: glm::Vec4) -> Self {
+        Self([v.x, v.y, v.z, v.w])
+    }
+}
+impl From<glm::IVec4> for IVec4 {
+    fn from(v: glm::IVec4) -> Self {
+        Self([v.x, v.y, v.z, v.w])
+    }
+}
+impl From<glm::UVec4> for UVec4 {
+    fn from(v: glm::UVec4) -> Self {
+        Self([v.x, v.y, v.z, v.w])
+    }
+}
+impl From<glm::Mat3> for Mat3 {
+    fn from(m: glm::Mat3) -> Self {
+        let c = |i: usize| [m[(0, i)], m[(1, i)], m[(2, i)], 0.0];
+        Self([c(0), c(1), c(2)])
+    }
+}
+impl From<glm::Mat4> for Mat4 {
+    fn from(m: glm::Mat4) -> Self {
+        let c = |i: usize| [m[(0, i)], m[(1, i)], m[(2, i)], m[(3, i)]];
+        Self([c(0), c(1), c(2), c(3)])
+    }
+}
+
+/// Declares a `repr(C)` struct that matches a `layout(std140)` uniform block.
+///
+/// Fails to compile if a field's offset isn't a multiple of its std140
+/// alignment, if the compiler slipped in padding of its own, or if the size
+/// isn't a multiple of 16. Add [`Padding`] fields to fix the layout.
+#[macro_export]
+macro_rules! std140_struct {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($field_vis:vis $field:ident : $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[repr(C)]
+        #[derive(Clone, Copy)]
+        $vis struct $name {
+            $($field_vis $field: $ty),*
+        }
+
+        const _: () = {
+            $(
+                assert!(
+                    ::core::mem::offset_of!($name, $field)
+                        % <$ty as $crate::std140::Std140Field>::ALIGN == 0,
+                    concat!("std140: `", stringify!($name), "::", stringify!($field),
+                            "` is misaligned, add Padding before it"),
+                );
+            )*
+            assert!(
+                ::core::mem::size_of::<$name>() == 0 $(+ ::core::mem::size_of::<$ty>())*,
+                concat!("std140: `", stringify!($name), "` has implicit padding"),
+            );
+            assert!(
+                ::core::mem::size_of::<$name>() % 16 == 0,
+                concat!("std140: `", stringify!($name), "` size must be a multiple of 16, add Padding at the end"),
+            );
+        };
+
+        unsafe impl $crate::bytemuck::Zeroable for $name {}
+        unsafe impl $crate::bytemuck::Pod for $name {}
+        unsafe impl $crate::std140::Std140 for $name {}
+    };
+}
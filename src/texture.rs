@@ -1,16 +1,21 @@
 use gl33::{*, global_loader::*};
-use image::ImageReader;
+use image::{DynamicImage, ImageReader};
+use image::imageops::FilterType;
 use super::unpack_enum as unpack_enum;
+use super::Error;
+use super::compressed::CompressedImage;
+use super::format::{self, ColorSpace, PixelFormat};
+use super::sampler::TextureParams;
 
 pub struct Texture(pub u32);
 impl Texture {
-    pub fn new() -> Option<Self> {
+    pub fn new() -> Result<Self, Error> {
         let mut tex = 0;
         unsafe { glGenTextures(1, &mut tex)};
         if tex != 0 {
-            Some(Self(tex))
+            Ok(Self(tex))
         } else {
-            None
+            Err(Error::Alloc("texture"))
         }
     }
 
@@ -21,29 +26,280 @@ impl Texture {
         }
     }
 
+    /// Repeat wrapping and linear filtering, the same as
+    /// `set_params(&TextureParams::default())`.
     pub fn setParams(&self) {
-        unsafe{
-        glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_S, unpack_enum(GL_REPEAT));
-        glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_T, unpack_enum(GL_REPEAT));
-        glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_MIN_FILTER, unpack_enum(GL_LINEAR));
-        glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_MAG_FILTER, unpack_enum(GL_LINEAR));
+        self.set_params(&TextureParams::default());
+    }
+
+    /// Sets how the texture gets sampled. Binds it to the active texture unit.
+    pub fn set_params(&self, params: &TextureParams) {
+        unsafe { glBindTexture(GL_TEXTURE_2D, self.0) };
+        params.apply(GL_TEXTURE_2D);
+    }
+
+    pub fn loadTexFile(&self, img_path: &str) -> Result<(), Error> {
+        self.load_file(img_path, &LoadOptions::default())
+    }
+
+    /// Loads an image file into the texture, flipped so the first row is the
+    /// bottom like GL expects. Binds it to the active texture unit.
+    pub fn load_file(&self, img_path: &str, options: &LoadOptions) -> Result<(), Error> {
+        let img = ImageReader::open(img_path)?.decode()?.flipv();
+        self.load_image(&img, options)
+    }
+
+    /// Uploads an image as level 0 and makes the mip levels `options` asks for.
+    pub fn load_image(&self, img: &DynamicImage, options: &LoadOptions) -> Result<(), Error> {
+        self.upload_with_mipmaps(img, options)?;
+        if let Some(swizzle) = format::gray_swizzle(img.color()).filter(|_| options.gray_swizzle) {
+            format::set_swizzle(GL_TEXTURE_2D, swizzle);
+        }
+        Ok(())
+    }
+
+    /// Picks which channel each of r, g, b and a samples from, `GL_RED`
+    /// through `GL_ALPHA`, `GL_ZERO` or `GL_ONE`.
+    pub fn set_swizzle(&self, swizzle: [GLenum; 4]) {
+        unsafe { glBindTexture(GL_TEXTURE_2D, self.0) };
+        format::set_swizzle(GL_TEXTURE_2D, swizzle);
+    }
+
+    /// Loads a KTX1 or DDS file of BC1 to BC5 blocks, see
+    /// [`load_compressed`](Self::load_compressed).
+    pub fn load_compressed_file(&self, path: impl AsRef<std::path::Path>, options: &LoadOptions) -> Result<(), Error> {
+        self.load_compressed(CompressedImage::open(path)?, options)
+    }
+
+    /// Uploads block compressed data with every mip level it has, flipped
+    /// like [`load_file`](Self::load_file) flips. The mip levels come from the
+    /// image, `options.mipmaps` is ignored.
+    ///
+    /// If the driver can't take the format, or the blocks can't be flipped,
+    /// the levels are decoded on the CPU and uploaded uncompressed instead.
+    /// It looks the same, it just takes 4 to 8 times the memory.
+    pub fn load_compressed(&self, mut img: CompressedImage, options: &LoadOptions) -> Result<(), Error> {
+        let srgb = img.srgb || options.color_space == ColorSpace::Srgb;
+        let flipped = img.flip_vertical();
+        unsafe { glBindTexture(GL_TEXTURE_2D, self.0) };
+        if flipped && img.format.is_supported(srgb) {
+            for (level, data) in img.levels.iter().enumerate() {
+                let (width, height) = img.level_size(level);
+                unsafe {
+                    glCompressedTexImage2D(GL_TEXTURE_2D, level as i32, img.format.gl_format(srgb),
+                        width as i32, height as i32, 0, data.len() as i32, data.as_ptr().cast());
+                }
+            }
+        } else {
+            let color_space = if srgb { ColorSpace::Srgb } else { ColorSpace::Linear };
+            for level in 0..img.levels.len() {
+                let decoded = img.decompress(level);
+                let decoded = if flipped { decoded } else { decoded.flipv() };
+                upload_level(GL_TEXTURE_2D, level as i32, &decoded, color_space)?;
+            }
         }
+        unsafe {
+            glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_BASE_LEVEL, 0);
+            glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_MAX_LEVEL, img.levels.len().saturating_sub(1) as i32);
+        }
+        Ok(())
     }
 
-    pub fn loadTexFile(&self, img_path: &str) {
-        let img = ImageReader::open(img_path).expect("Bad Texture Image Path").decode().expect("Image corrupt?").flipv();
-        let data_format = match img.color() {
-            image::ColorType::Rgb8 => GL_RGB,
-            image::ColorType::Rgba8 => GL_RGBA,
-            _ => {panic!("Not supported Image format")},
+    fn upload_with_mipmaps(&self, img: &DynamicImage, options: &LoadOptions) -> Result<(), Error> {
+        match options.mipmaps {
+            Mipmaps::None => self.upload_mip_chain(std::slice::from_ref(img), options.color_space),
+            Mipmaps::Generate => {
+                unsafe { glBindTexture(GL_TEXTURE_2D, self.0) };
+                upload_level(GL_TEXTURE_2D, 0, img, options.color_space)?;
+                unsafe {
+                    // back to GL's default so every level gets generated
+                    glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_BASE_LEVEL, 0);
+                    glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_MAX_LEVEL, 1000);
+                    glGenerateMipmap(GL_TEXTURE_2D);
+                }
+                Ok(())
+            }
+            Mipmaps::Cpu(filter) => self.upload_mip_chain(&mip_chain(img, filter), options.color_space),
+        }
+    }
+
+    /// Uploads `levels[0]` as the base level and each following image as the
+    /// next mip level. Every level should be half the size of the one before,
+    /// rounded down but at least 1. `GL_TEXTURE_MAX_LEVEL` is set to the last
+    /// level so a short chain still counts as complete.
+    pub fn upload_mip_chain(&self, levels: &[DynamicImage], color_space: ColorSpace) -> Result<(), Error> {
+        unsafe { glBindTexture(GL_TEXTURE_2D, self.0) };
+        for (level, img) in levels.iter().enumerate() {
+            upload_level(GL_TEXTURE_2D, level as i32, img, color_space)?;
+        }
+        unsafe {
+            glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_BASE_LEVEL, 0);
+            glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_MAX_LEVEL, levels.len().saturating_sub(1) as i32);
+        }
+        Ok(())
+    }
+
+    /// Reads a mip level back with `glGetTexImage`, flipped so the first row
+    /// is the top again, the way the image was before [`load_file`](Self::load_file).
+    ///
+    /// One and two channel 8 and 16 bit textures come back as luma and
+    /// luma alpha images, other 16 bit ones as RGBA16, float and depth textures as RGBA32F and
+    /// everything else, compressed and sRGB included, as RGBA8. sRGB data
+    /// comes back still encoded. Binds the texture to the active texture unit.
+    pub fn read_pixels(&self, level: u32) -> Result<DynamicImage, Error> {
+        let (mut width, mut height, mut internal_format) = (0, 0, 0);
+        unsafe {
+            glBindTexture(GL_TEXTURE_2D, self.0);
+            glGetTexLevelParameteriv(GL_TEXTURE_2D, level as i32, GL_TEXTURE_WIDTH, &mut width);
+            glGetTexLevelParameteriv(GL_TEXTURE_2D, level as i32, GL_TEXTURE_HEIGHT, &mut height);
+            glGetTexLevelParameteriv(GL_TEXTURE_2D, level as i32, GL_TEXTURE_INTERNAL_FORMAT, &mut internal_format);
+        }
+        if width == 0 || height == 0 {
+            return Err(Error::TextureSize(format!("level {} of texture {} is empty", level, self.0)));
+        }
+        let (width, height) = (width as u32, height as u32);
+        let read = |format: PixelFormat, data: *mut std::ffi::c_void| {
+            format::set_pack_alignment(width as usize * format.bytes_per_pixel);
+            unsafe { glGetTexImage(GL_TEXTURE_2D, level as i32, format.format, format.ty, data) };
         };
-        unsafe{
-        glTexImage2D(GL_TEXTURE_2D, 0, unpack_enum(data_format), 
-                        img.width() as i32, img.height() as i32, 0, data_format,
-                         GL_UNSIGNED_BYTE, img.as_bytes().as_ptr() as *const _);
+        let img = match GLenum(internal_format as u32) {
+            GL_R8 => {
+                let mut img = image::GrayImage::new(width, height);
+                read(PixelFormat::R8, img.as_mut_ptr().cast());
+                DynamicImage::ImageLuma8(img)
+            }
+            GL_R16 => {
+                let mut img = image::ImageBuffer::<image::Luma<u16>, _>::new(width, height);
+                read(PixelFormat::R16, img.as_mut_ptr().cast());
+                DynamicImage::ImageLuma16(img)
+            }
+            // gray and alpha images are uploaded as RG, the swizzle that
+            // shows them as gray doesn't apply to glGetTexImage
+            GL_RG8 => {
+                let mut img = image::GrayAlphaImage::new(width, height);
+                read(PixelFormat::RG8, img.as_mut_ptr().cast());
+                DynamicImage::ImageLumaA8(img)
+            }
+            GL_RG16 => {
+                let mut img = image::ImageBuffer::<image::LumaA<u16>, _>::new(width, height);
+                read(PixelFormat::RG16, img.as_mut_ptr().cast());
+                DynamicImage::ImageLumaA16(img)
+            }
+            GL_RGB16 | GL_RGBA16 => {
+                let mut img = image::ImageBuffer::<image::Rgba<u16>, _>::new(width, height);
+                read(PixelFormat::RGBA16, img.as_mut_ptr().cast());
+                DynamicImage::ImageRgba16(img)
+            }
+            GL_R16F | GL_RG16F | GL_RGB16F | GL_RGBA16F | GL_R32F | GL_RG32F | GL_RGB32F | GL_RGBA32F
+            | GL_R11F_G11F_B10F => {
+                let mut img = image::Rgba32FImage::new(width, height);
+                read(PixelFormat::RGBA32F, img.as_mut_ptr().cast());
+                DynamicImage::ImageRgba32F(img)
+            }
+            GL_DEPTH_COMPONENT16 | GL_DEPTH_COMPONENT24 | GL_DEPTH_COMPONENT32 | GL_DEPTH_COMPONENT32F => {
+                let mut depth = vec![0f32; (width * height) as usize];
+                let format = PixelFormat { format: GL_DEPTH_COMPONENT, ..PixelFormat::R32F };
+                read(format, depth.as_mut_ptr().cast());
+                DynamicImage::ImageRgba32F(image::Rgba32FImage::from_fn(width, height, |x, y| {
+                    let d = depth[(y * width + x) as usize];
+                    image::Rgba([d, d, d, 1.0])
+                }))
+            }
+            _ => {
+                let mut img = image::RgbaImage::new(width, height);
+                read(PixelFormat::RGBA8, img.as_mut_ptr().cast());
+                DynamicImage::ImageRgba8(img)
+            }
+        };
+        Ok(img.flipv())
+    }
+
+    /// Saves a mip level as a PNG, see [`read_pixels`](Self::read_pixels).
+    /// PNGs can't hold floats, so float textures get clamped to 0..1 and
+    /// saved as 8 bit.
+    pub fn save_png(&self, level: u32, path: impl AsRef<std::path::Path>) -> Result<(), Error> {
+        let mut img = self.read_pixels(level)?;
+        if matches!(img, DynamicImage::ImageRgba32F(_)) {
+            img = DynamicImage::ImageRgba8(img.to_rgba8());
         }
+        img.save_with_format(path, image::ImageFormat::Png)?;
+        Ok(())
+    }
+}
+
+/// Uploads one level of the texture bound to `target`, or of one cube map face.
+pub(crate) fn upload_level(target: GLenum, level: i32, img: &DynamicImage, color_space: ColorSpace) -> Result<(), Error> {
+    let format = PixelFormat::of(img.color())?.in_color_space(color_space);
+    format::set_unpack_alignment(img.width() as usize * format.bytes_per_pixel);
+    unsafe{
+    glTexImage2D(target, level, unpack_enum(format.internal_format),
+                    img.width() as i32, img.height() as i32, 0, format.format,
+                     format.ty, img.as_bytes().as_ptr() as *const _);
+    }
+    Ok(())
+}
+
+/// Where a texture's mip levels come from.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Mipmaps {
+    /// Only level 0, sample it with a non-mipmap [`MinFilter`](crate::sampler::MinFilter).
+    #[default]
+    None,
+    /// `glGenerateMipmap` after the upload.
+    Generate,
+    /// Downsample on the CPU with the given filter and upload every level.
+    /// `Lanczos3` keeps more detail in the smaller levels than `Triangle`,
+    /// at the cost of some ringing around hard edges.
+    Cpu(FilterType),
+}
+
+/// Settings for loading an image into a [`Texture`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoadOptions {
+    pub mipmaps: Mipmaps,
+    /// Swizzle grayscale images so they sample as gray instead of red, see
+    /// [`gray_swizzle`](format::gray_swizzle). On by default.
+    pub gray_swizzle: bool,
+    /// Linear by default, use [`ColorSpace::Srgb`] for color textures.
+    pub color_space: ColorSpace,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        Self { mipmaps: Mipmaps::None, gray_swizzle: true, color_space: ColorSpace::Linear }
+    }
+}
+
+impl LoadOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn mipmaps(self, mipmaps: Mipmaps) -> Self {
+        Self { mipmaps, ..self }
     }
 
+    pub fn gray_swizzle(self, gray_swizzle: bool) -> Self {
+        Self { gray_swizzle, ..self }
+    }
+
+    pub fn color_space(self, color_space: ColorSpace) -> Self {
+        Self { color_space, ..self }
+    }
+}
+
+/// Makes the full mip chain for `img`, from the image itself down to 1x1.
+pub fn mip_chain(img: &DynamicImage, filter: FilterType) -> Vec<DynamicImage> {
+    let mut levels = vec![img.clone()];
+    let (mut width, mut height) = (img.width(), img.height());
+    while width > 1 || height > 1 {
+        width = (width / 2).max(1);
+        height = (height / 2).max(1);
+        // always from the previous level, so each step only halves
+        let next = levels.last().unwrap().resize_exact(width, height, filter);
+        levels.push(next);
+    }
+    levels
 }
 
 impl Drop for Texture {
@@ -0,0 +1,185 @@
+//! Hands out texture units so sampler uniforms don't need hard coded
+//! `GL_TEXTUREn` numbers.
+//!
+//! ```ignore
+//! let mut units = TextureUnits::new();
+//! // every frame, with the program in use
+//! units.reset();
+//! units.bind(&program, "brick", &brick)?;
+//! units.bind(&program, "face", &face)?;
+//! ```
+
+use gl33::{*, global_loader::*};
+
+use super::Error;
+use super::cubemap::CubeMap;
+use super::layered::{Texture2DArray, Texture3D};
+use super::shader::ShaderProgram;
+use super::texture::Texture;
+
+/// A texture that can sit on a texture unit.
+pub trait Bindable {
+    /// The target it binds to, eg `GL_TEXTURE_2D`.
+    fn target(&self) -> GLenum;
+    /// The texture object name.
+    fn id(&self) -> u32;
+}
+
+impl Bindable for Texture {
+    fn target(&self) -> GLenum {
+        GL_TEXTURE_2D
+    }
+    fn id(&self) -> u32 {
+        self.0
+    }
+}
+
+impl Bindable for CubeMap {
+    fn target(&self) -> GLenum {
+        GL_TEXTURE_CUBE_MAP
+    }
+    fn id(&self) -> u32 {
+        self.0
+    }
+}
+
+impl Bindable for Texture2DArray {
+    fn target(&self) -> GLenum {
+        GL_TEXTURE_2D_ARRAY
+    }
+    fn id(&self) -> u32 {
+        Texture2DArray::id(self)
+    }
+}
+
+impl Bindable for Texture3D {
+    fn target(&self) -> GLenum {
+        GL_TEXTURE_3D
+    }
+    fn id(&self) -> u32 {
+        Texture3D::id(self)
+    }
+}
+
+/// Tracks which texture units are taken for the next draw.
+///
+/// Call [`reset`](Self::reset) before setting up each draw, then
+/// [`bind`](Self::bind) each texture to its sampler. A texture bound to two
+/// samplers only takes one unit.
+#[derive(Debug, Clone)]
+pub struct TextureUnits {
+    /// `(target, id)` of the texture on each unit taken so far.
+    bound: Vec<(GLenum, u32)>,
+    max: u32,
+}
+
+// no Default, `new` asks GL and needs a current context
+#[allow(clippy::new_without_default)]
+impl TextureUnits {
+    /// Asks GL how many units the fragment shader can use,
+    /// `GL_MAX_TEXTURE_IMAGE_UNITS`, at least 16 in GL 3.3.
+    pub fn new() -> Self {
+        let mut max = 0;
+        unsafe { glGetIntegerv(GL_MAX_TEXTURE_IMAGE_UNITS, &mut max) };
+        Self::with_max(max.max(0) as u32)
+    }
+
+    /// Hands out at most `max` units.
+    pub fn with_max(max: u32) -> Self {
+        Self { bound: Vec::new(), max }
+    }
+
+    /// Frees every unit, the textures stay bound until they're replaced.
+    pub fn reset(&mut self) {
+        self.bound.clear();
+    }
+
+    /// How many units are taken.
+    pub fn used(&self) -> u32 {
+        self.bound.len() as u32
+    }
+
+    /// Binds `texture` to a free unit and points the `sampler` uniform at it,
+    /// returning the unit number. The program needs to be the one in use.
+    ///
+    /// Errors if `sampler` isn't an active sampler uniform for the texture's
+    /// target, eg a `samplerCube` given a 2D texture, or every unit is already
+    /// taken since the last [`reset`](Self::reset).
+    pub fn bind(&mut self, program: &ShaderProgram, sampler: &str, texture: &impl Bindable) -> Result<u32, Error> {
+        let u = match program.uniform(sampler) {
+            Some(u) if u.location != -1 => *u,
+            _ => return Err(Error::MissingUniform(sampler.to_string())),
+        };
+        if sampler_target(u.ty) != Some(texture.target()) {
+            return Err(Error::UniformType {
+                name: sampler.to_string(),
+                expected: u.ty,
+                found: sampler_type(texture.target()),
+            });
+        }
+
+        let key = (texture.target(), texture.id());
+        let unit = match self.bound.iter().position(|&b| b == key) {
+            Some(unit) => unit as u32,
+            None => {
+                if self.used() >= self.max {
+                    return Err(Error::OutOfTextureUnits { sampler: sampler.to_string(), max: self.max });
+                }
+                let unit = self.used();
+                unsafe {
+                    glActiveTexture(GLenum(GL_TEXTURE0.0 + unit));
+                    glBindTexture(key.0, key.1);
+                }
+                self.bound.push(key);
+                unit
+            }
+        };
+        program.set_uniform(sampler, &(unit as i32))?;
+        Ok(unit)
+    }
+}
+
+/// The texture target a sampler type reads from, counting the int, unsigned
+/// and shadow variants. `None` for anything [`Bindable`] doesn't cover.
+fn sampler_target(ty: GLenum) -> Option<GLenum> {
+    match ty {
+        GL_SAMPLER_2D | GL_INT_SAMPLER_2D | GL_UNSIGNED_INT_SAMPLER_2D | GL_SAMPLER_2D_SHADOW => Some(GL_TEXTURE_2D),
+        GL_SAMPLER_CUBE | GL_INT_SAMPLER_CUBE | GL_UNSIGNED_INT_SAMPLER_CUBE | GL_SAMPLER_CUBE_SHADOW => {
+            Some(GL_TEXTURE_CUBE_MAP)
+        }
+        GL_SAMPLER_2D_ARRAY | GL_INT_SAMPLER_2D_ARRAY | GL_UNSIGNED_INT_SAMPLER_2D_ARRAY | GL_SAMPLER_2D_ARRAY_SHADOW => {
+            Some(GL_TEXTURE_2D_ARRAY)
+        }
+        GL_SAMPLER_3D | GL_INT_SAMPLER_3D | GL_UNSIGNED_INT_SAMPLER_3D => Some(GL_TEXTURE_3D),
+        _ => None,
+    }
+}
+
+/// The plain float sampler type for a texture target, for error messages.
+fn sampler_type(target: GLenum) -> GLenum {
+    match target {
+        GL_TEXTURE_CUBE_MAP => GL_SAMPLER_CUBE,
+        GL_TEXTURE_2D_ARRAY => GL_SAMPLER_2D_ARRAY,
+        GL_TEXTURE_3D => GL_SAMPLER_3D,
+        _ => GL_SAMPLER_2D,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sampler_variants_match_their_target() {
+        for target in [GL_TEXTURE_2D, GL_TEXTURE_CUBE_MAP, GL_TEXTURE_2D_ARRAY, GL_TEXTURE_3D] {
+            assert_eq!(sampler_target(sampler_type(target)), Some(target));
+        }
+        assert_eq!(sampler_target(GL_UNSIGNED_INT_SAMPLER_2D), Some(GL_TEXTURE_2D));
+        assert_eq!(sampler_target(GL_SAMPLER_2D_SHADOW), Some(GL_TEXTURE_2D));
+        assert_eq!(sampler_target(GL_INT_SAMPLER_CUBE), Some(GL_TEXTURE_CUBE_MAP));
+        assert_eq!(sampler_target(GL_SAMPLER_2D_ARRAY_SHADOW), Some(GL_TEXTURE_2D_ARRAY));
+        assert_ne!(sampler_target(GL_SAMPLER_CUBE), Some(GL_TEXTURE_2D));
+        assert_eq!(sampler_target(GL_SAMPLER_1D), None);
+        assert_eq!(sampler_target(GL_FLOAT_VEC4), None);
+    }
+}
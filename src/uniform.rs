@@ -0,0 +1,148 @@
+use gl33::{*, global_loader::*};
+use nalgebra_glm as glm;
+
+use super::unpack_enum as unpack_enum;
+
+/// A Rust type that matches one GLSL uniform type.
+///
+/// Use it through [`ShaderProgram::set_uniform`](crate::shader::ShaderProgram::set_uniform),
+/// which checks `GL_TYPE` against the type the program reports for the uniform.
+pub trait Uniform: Sized {
+    /// The GLSL type as reported by `glGetActiveUniform`, eg `GL_FLOAT_VEC3`.
+    const GL_TYPE: GLenum;
+
+    /// Uploads `vals` to `location` and the array elements that follow it.
+    fn upload(location: i32, vals: &[Self]);
+}
+
+/// Anything [`ShaderProgram::set_uniform`](crate::shader::ShaderProgram::set_uniform)
+/// takes, a single [`Uniform`] or a slice of them for array uniforms.
+pub trait UniformValue {
+    fn gl_type(&self) -> GLenum;
+    fn count(&self) -> usize;
+    fn upload(&self, location: i32);
+}
+
+impl<T: Uniform> UniformValue for T {
+    fn gl_type(&self) -> GLenum {
+        T::GL_TYPE
+    }
+    fn count(&self) -> usize {
+        1
+    }
+    fn upload(&self, location: i32) {
+        T::upload(location, std::slice::from_ref(self));
+    }
+}
+
+impl<T: Uniform> UniformValue for [T] {
+    fn gl_type(&self) -> GLenum {
+        T::GL_TYPE
+    }
+    fn count(&self) -> usize {
+        self.len()
+    }
+    fn upload(&self, location: i32) {
+        T::upload(location, self);
+    }
+}
+
+/// Checks if a value of type `value` can be uploaded to a uniform of type
+/// `uniform`. Samplers are set with the `int` texture unit.
+pub fn types_compatible(value: GLenum, uniform: GLenum) -> bool {
+    value == uniform || (value == GL_INT && is_sampler(uniform))
+}
+
+/// Checks if a reflected uniform type is one of the sampler types.
+pub fn is_sampler(ty: GLenum) -> bool {
+    matches!(
+        ty,
+        GL_SAMPLER_1D | GL_SAMPLER_2D | GL_SAMPLER_3D | GL_SAMPLER_CUBE
+            | GL_SAMPLER_1D_SHADOW | GL_SAMPLER_2D_SHADOW | GL_SAMPLER_CUBE_SHADOW
+            | GL_SAMPLER_1D_ARRAY | GL_SAMPLER_2D_ARRAY
+            | GL_SAMPLER_1D_ARRAY_SHADOW | GL_SAMPLER_2D_ARRAY_SHADOW
+            | GL_SAMPLER_2D_MULTISAMPLE | GL_SAMPLER_2D_MULTISAMPLE_ARRAY
+            | GL_SAMPLER_BUFFER | GL_SAMPLER_2D_RECT | GL_SAMPLER_2D_RECT_SHADOW
+            | GL_INT_SAMPLER_1D | GL_INT_SAMPLER_2D | GL_INT_SAMPLER_3D | GL_INT_SAMPLER_CUBE
+            | GL_INT_SAMPLER_1D_ARRAY | GL_INT_SAMPLER_2D_ARRAY
+            | GL_INT_SAMPLER_2D_MULTISAMPLE | GL_INT_SAMPLER_2D_MULTISAMPLE_ARRAY
+            | GL_INT_SAMPLER_BUFFER | GL_INT_SAMPLER_2D_RECT
+            | GL_UNSIGNED_INT_SAMPLER_1D | GL_UNSIGNED_INT_SAMPLER_2D
+            | GL_UNSIGNED_INT_SAMPLER_3D | GL_UNSIGNED_INT_SAMPLER_CUBE
+            | GL_UNSIGNED_INT_SAMPLER_1D_ARRAY | GL_UNSIGNED_INT_SAMPLER_2D_ARRAY
+            | GL_UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE | GL_UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE_ARRAY
+            | GL_UNSIGNED_INT_SAMPLER_BUFFER | GL_UNSIGNED_INT_SAMPLER_2D_RECT
+    )
+}
+
+/// Types that are laid out as `N` plain components, so a slice of them can
+/// go straight to a `glUniform*v` call.
+macro_rules! impl_uniform {
+    ($ty:ty, $gl_type:expr, $component:ty, $func:ident) => {
+        impl Uniform for $ty {
+            const GL_TYPE: GLenum = $gl_type;
+            fn upload(location: i32, vals: &[Self]) {
+                unsafe { $func(location, vals.len() as i32, vals.as_ptr() as *const $component) }
+            }
+        }
+    };
+}
+
+/// Same as `impl_uniform` but for the `glUniformMatrix*v` calls. glm matrices
+/// are column major like GL expects, so they're never transposed.
+macro_rules! impl_uniform_matrix {
+    ($ty:ty, $gl_type:expr, $func:ident) => {
+        impl Uniform for $ty {
+            const GL_TYPE: GLenum = $gl_type;
+            fn upload(location: i32, vals: &[Self]) {
+                unsafe {
+                    $func(location, vals.len() as i32, unpack_enum(GL_FALSE) as u8, vals.as_ptr() as *const f32)
+                }
+            }
+        }
+    };
+}
+
+/// GLSL bools are uploaded as ints, a Rust `bool` is a byte so it has to be
+/// widened first.
+macro_rules! impl_uniform_bool {
+    ($ty:ty, $gl_type:expr, $func:ident) => {
+        impl Uniform for $ty {
+            const GL_TYPE: GLenum = $gl_type;
+            fn upload(location: i32, vals: &[Self]) {
+                let ints: Vec<i32> = vals.iter().flat_map(|v| v.iter().map(|&b| b as i32)).collect();
+                unsafe { $func(location, vals.len() as i32, ints.as_ptr()) }
+            }
+        }
+    };
+}
+
+impl_uniform!(f32, GL_FLOAT, f32, glUniform1fv);
+impl_uniform!(glm::Vec2, GL_FLOAT_VEC2, f32, glUniform2fv);
+impl_uniform!(glm::Vec3, GL_FLOAT_VEC3, f32, glUniform3fv);
+impl_uniform!(glm::Vec4, GL_FLOAT_VEC4, f32, glUniform4fv);
+
+impl_uniform!(i32, GL_INT, i32, glUniform1iv);
+impl_uniform!(glm::IVec2, GL_INT_VEC2, i32, glUniform2iv);
+impl_uniform!(glm::IVec3, GL_INT_VEC3, i32, glUniform3iv);
+impl_uniform!(glm::IVec4, GL_INT_VEC4, i32, glUniform4iv);
+
+impl_uniform!(u32, GL_UNSIGNED_INT, u32, glUniform1uiv);
+impl_uniform!(glm::UVec2, GL_UNSIGNED_INT_VEC2, u32, glUniform2uiv);
+impl_uniform!(glm::UVec3, GL_UNSIGNED_INT_VEC3, u32, glUniform3uiv);
+impl_uniform!(glm::UVec4, GL_UNSIGNED_INT_VEC4, u32, glUniform4uiv);
+
+impl_uniform_matrix!(glm::Mat2, GL_FLOAT_MAT2, glUniformMatrix2fv);
+impl_uniform_matrix!(glm::Mat3, GL_FLOAT_MAT3, glUniformMatrix3fv);
+impl_uniform_matrix!(glm::Mat4, GL_FLOAT_MAT4, glUniformMatrix4fv);
+
+impl Uniform for bool {
+    const GL_TYPE: GLenum = GL_BOOL;
+    fn upload(location: i32, vals: &[Self]) {
+        let ints: Vec<i32> = vals.iter().map(|&b| b as i32).collect();
+        unsafe { glUniform1iv(location, vals.len() as i32, ints.as_ptr()) }
+    }
+}
+impl_uniform_bool!(glm::BVec2, GL_BOOL_VEC2, glUniform2iv);
+impl_uniform_bool!(glm::BVec3, GL_BOOL_VEC3, glUniform3iv);
+impl_uniform_bool!(glm::BVec4, GL_BOOL_VEC4, glUniform4iv);
@@ -0,0 +1,39 @@
+use super::{BufferObject, BufferType, Error};
+use super::buffer::{Buffer, Usage};
+use super::std140::Std140;
+
+/// A [Uniform Buffer
+/// Object](https://www.khronos.org/opengl/wiki/Uniform_Buffer_Object) holding
+/// one `T`.
+///
+/// Bind it to a binding point with
+/// [`UniformBuffer::bind_base`](UniformBuffer::bind_base), and point each
+/// program's block at the same binding with
+/// [`ShaderProgram::bind_uniform_block`](crate::shader::ShaderProgram::bind_uniform_block).
+/// Every program then reads the same data and it only gets uploaded once.
+pub struct UniformBuffer<T: Std140> {
+    buffer: Buffer<T>,
+}
+
+impl<T: Std140> UniformBuffer<T> {
+    /// Makes the buffer and uploads the starting value.
+    pub fn new(value: &T) -> Result<Self, Error> {
+        let buffer = Buffer::from_slice(BufferType::Uniform, std::slice::from_ref(value), Usage::DynamicDraw)?;
+        Ok(Self { buffer })
+    }
+
+    /// Replaces the contents of the buffer.
+    pub fn update(&self, value: &T) {
+        self.buffer.update(0, std::slice::from_ref(value)).expect("the buffer holds one value");
+    }
+
+    /// Attaches the buffer to a uniform buffer binding point.
+    pub fn bind_base(&self, binding: u32) {
+        self.buffer.bind_base(binding);
+    }
+
+    /// The underlying buffer object.
+    pub fn buffer(&self) -> &BufferObject {
+        self.buffer.buffer()
+    }
+}
@@ -0,0 +1,394 @@
+//! Describing vertex data so a [`VertexArray`](crate::VertexArray) can set up
+//! its attributes, instead of hand written `glVertexAttribPointer` calls.
+//!
+//! ```ignore
+//! vertex_struct! {
+//!     struct Vertex {
+//!         pos: [f32; 3],                  // location 0
+//!         uv: [f32; 2],                   // location 1
+//!         color: Normalized<[u8; 4]>,     // location 2, a vec4 in 0..1
+//!     }
+//! }
+//! vao.set_layout(&vbo, &Vertex::layout());
+//! Vertex::layout().validate(&program)?;
+//! ```
+
+use std::collections::HashMap;
+
+use bytemuck::{Pod, Zeroable};
+use gl33::{*, global_loader::*};
+
+use super::Error;
+use super::shader::{ActiveVariable, ShaderProgram};
+
+/// One attribute, what one `glVertexAttribPointer` call sets up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VertexAttribute {
+    /// The `layout (location = N)` of the shader input.
+    pub location: u32,
+    /// How many components per vertex, 1 to 4.
+    pub components: i32,
+    /// The type of each component in the buffer, eg `GL_FLOAT` or
+    /// `GL_UNSIGNED_BYTE`.
+    pub ty: GLenum,
+    /// Map integer data to 0..1 (or -1..1 when signed) instead of converting
+    /// it straight to float.
+    pub normalized: bool,
+    /// Keep integer data as integers for `int`/`uint` shader inputs, set up
+    /// with `glVertexAttribIPointer`.
+    pub integer: bool,
+    /// Bytes from the start of the vertex.
+    pub offset: usize,
+    /// 0 to move to the next element every vertex, `n` to move every `n`
+    /// instances when drawing instanced.
+    pub divisor: u32,
+}
+
+impl VertexAttribute {
+    /// `components` values of type `ty`, converted to float for the shader.
+    pub fn new(location: u32, components: i32, ty: GLenum, offset: usize) -> Self {
+        Self { location, components, ty, normalized: false, integer: false, offset, divisor: 0 }
+    }
+
+    /// `components` floats.
+    pub fn float(location: u32, components: i32, offset: usize) -> Self {
+        Self::new(location, components, GL_FLOAT, offset)
+    }
+
+    pub fn normalized(self, normalized: bool) -> Self {
+        Self { normalized, ..self }
+    }
+
+    pub fn integer(self, integer: bool) -> Self {
+        Self { integer, ..self }
+    }
+
+    pub fn divisor(self, divisor: u32) -> Self {
+        Self { divisor, ..self }
+    }
+}
+
+/// Every attribute of one interleaved vertex buffer.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct VertexLayout {
+    /// Bytes from one vertex to the next.
+    pub stride: usize,
+    pub attributes: Vec<VertexAttribute>,
+}
+
+impl VertexLayout {
+    pub fn new(stride: usize) -> Self {
+        Self { stride, attributes: Vec::new() }
+    }
+
+    pub fn attribute(mut self, attribute: VertexAttribute) -> Self {
+        self.attributes.push(attribute);
+        self
+    }
+
+    /// Adds a field of type `F` at `offset`, on the location after the last
+    /// one used. Matrices take one location per column.
+    pub fn field<F: VertexField>(mut self, offset: usize) -> Self {
+        let first = self.next_location();
+        let column_bytes = size_of::<F>() / F::LOCATIONS as usize;
+        for column in 0..F::LOCATIONS {
+            self = self.attribute(VertexAttribute {
+                location: first + column,
+                components: F::COMPONENTS,
+                ty: F::TY,
+                normalized: F::NORMALIZED,
+                integer: F::INTEGER,
+                offset: offset + column as usize * column_bytes,
+                divisor: 0,
+            });
+        }
+        self
+    }
+
+    /// Moves every attribute up by `first` locations, for a second buffer
+    /// whose layout would otherwise start at 0 too.
+    pub fn at_location(mut self, first: u32) -> Self {
+        for a in &mut self.attributes {
+            a.location += first;
+        }
+        self
+    }
+
+    /// Sets the divisor of every attribute, 1 for a buffer of per instance
+    /// data.
+    pub fn divisor(mut self, divisor: u32) -> Self {
+        for a in &mut self.attributes {
+            a.divisor = divisor;
+        }
+        self
+    }
+
+    /// One past the highest location in the layout.
+    pub fn next_location(&self) -> u32 {
+        self.attributes.iter().map(|a| a.location + 1).max().unwrap_or(0)
+    }
+
+    /// Points every attribute at the buffer bound to `GL_ARRAY_BUFFER` and
+    /// enables it, for the bound VAO.
+    pub(crate) fn apply(&self) {
+        let stride = self.stride as i32;
+        for a in &self.attributes {
+            unsafe {
+                if a.integer {
+                    glVertexAttribIPointer(a.location, a.components, a.ty, stride, a.offset as *const _);
+                } else {
+                    glVertexAttribPointer(a.location, a.components, a.ty, a.normalized as u8, stride, a.offset as *const _);
+                }
+                glVertexAttribDivisor(a.location, a.divisor);
+                glEnableVertexAttribArray(a.location);
+            }
+        }
+    }
+
+    /// Checks the layout against the program's active attributes: every
+    /// location the program reads has to be in the layout with at most as
+    /// many components as the input has (per column for matrices, GL fills
+    /// in missing ones from `(0, 0, 0, 1)`), and `int`/`uint` inputs need
+    /// integer attributes while float inputs need the rest.
+    pub fn validate(&self, program: &ShaderProgram) -> Result<(), Error> {
+        self.check_inputs(&program.reflection().attributes)
+    }
+
+    fn check_inputs(&self, inputs: &HashMap<String, ActiveVariable>) -> Result<(), Error> {
+        for a in &self.attributes {
+            if a.integer && a.ty == GL_FLOAT {
+                return Err(Error::VertexLayout(format!("location {} is integer but holds floats", a.location)));
+            }
+            if a.integer && a.normalized {
+                return Err(Error::VertexLayout(format!("location {} is both integer and normalized", a.location)));
+            }
+            if !(1..=4).contains(&a.components) {
+                return Err(Error::VertexLayout(format!("location {} has {} components", a.location, a.components)));
+            }
+        }
+        let mut inputs: Vec<_> = inputs.iter().collect();
+        inputs.sort_by_key(|(_, v)| v.location);
+        for (name, input) in inputs {
+            // built ins like gl_VertexID don't come from buffers
+            if input.location < 0 {
+                continue;
+            }
+            let integer = is_integer(input.ty);
+            for location in input.location as u32..input.location as u32 + locations(input.ty) {
+                let Some(a) = self.attributes.iter().find(|a| a.location == location) else {
+                    return Err(Error::VertexLayout(format!("`{}` reads location {} but the layout doesn't set it", name, location)));
+                };
+                if a.integer != integer {
+                    let (shader, layout) = if integer { ("integer", "float") } else { ("float", "integer") };
+                    return Err(Error::VertexLayout(format!(
+                        "`{}` at location {} is {} but the layout gives it {} data",
+                        name, location, shader, layout
+                    )));
+                }
+                if a.components > components(input.ty) {
+                    return Err(Error::VertexLayout(format!(
+                        "`{}` at location {} has {} components but the layout gives it {}",
+                        name,
+                        location,
+                        components(input.ty),
+                        a.components
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A `#[repr(C)]` vertex type, usually declared with
+/// [`vertex_struct!`](crate::vertex_struct).
+pub trait Vertex: Pod {
+    fn layout() -> VertexLayout;
+}
+
+/// A Rust type that can be one attribute of a [`Vertex`].
+pub trait VertexField: Pod {
+    const TY: GLenum;
+    /// Components per location.
+    const COMPONENTS: i32;
+    const NORMALIZED: bool;
+    const INTEGER: bool;
+    /// How many locations it takes, the number of columns for matrices.
+    const LOCATIONS: u32 = 1;
+}
+
+/// The scalar types an attribute can be made of.
+pub trait Component: Pod {
+    const TY: GLenum;
+    /// Whether it reaches the shader as an integer when not normalized.
+    const INTEGER: bool;
+}
+
+macro_rules! impl_component {
+    ($($ty:ty => $gl:expr, $integer:expr;)*) => {
+        $(impl Component for $ty {
+            const TY: GLenum = $gl;
+            const INTEGER: bool = $integer;
+        })*
+    };
+}
+
+impl_component! {
+    f32 => GL_FLOAT, false;
+    i8 => GL_BYTE, true;
+    u8 => GL_UNSIGNED_BYTE, true;
+    i16 => GL_SHORT, true;
+    u16 => GL_UNSIGNED_SHORT, true;
+    i32 => GL_INT, true;
+    u32 => GL_UNSIGNED_INT, true;
+}
+
+impl<C: Component> VertexField for C {
+    const TY: GLenum = C::TY;
+    const COMPONENTS: i32 = 1;
+    const NORMALIZED: bool = false;
+    const INTEGER: bool = C::INTEGER;
+}
+
+impl<C: Component, const N: usize> VertexField for [C; N]
+where
+    [C; N]: Pod,
+{
+    const TY: GLenum = C::TY;
+    const COMPONENTS: i32 = N as i32;
+    const NORMALIZED: bool = false;
+    const INTEGER: bool = C::INTEGER;
+}
+
+/// Column major matrices, `[[f32; 4]; 4]` is a `mat4` and takes 4
+/// locations. glm matrices convert to these with `into()`.
+impl<C: Component, const N: usize, const M: usize> VertexField for [[C; N]; M]
+where
+    [[C; N]; M]: Pod,
+{
+    const TY: GLenum = C::TY;
+    const COMPONENTS: i32 = N as i32;
+    const NORMALIZED: bool = false;
+    const INTEGER: bool = C::INTEGER;
+    const LOCATIONS: u32 = M as u32;
+}
+
+/// Integer data the shader sees as floats in 0..1, or -1..1 for signed
+/// types. `Normalized<[u8; 4]>` is the usual packed color.
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Normalized<T>(pub T);
+unsafe impl<T: Zeroable> Zeroable for Normalized<T> {}
+unsafe impl<T: Pod> Pod for Normalized<T> {}
+
+impl<C: Component, const N: usize> VertexField for Normalized<[C; N]>
+where
+    [C; N]: Pod,
+{
+    const TY: GLenum = C::TY;
+    const COMPONENTS: i32 = N as i32;
+    const NORMALIZED: bool = true;
+    const INTEGER: bool = false;
+}
+
+fn is_integer(ty: GLenum) -> bool {
+    matches!(
+        ty,
+        GL_INT | GL_INT_VEC2 | GL_INT_VEC3 | GL_INT_VEC4
+            | GL_UNSIGNED_INT | GL_UNSIGNED_INT_VEC2 | GL_UNSIGNED_INT_VEC3 | GL_UNSIGNED_INT_VEC4
+    )
+}
+
+/// How many locations an input of type `ty` takes, one per matrix column.
+#[allow(non_upper_case_globals)]
+fn locations(ty: GLenum) -> u32 {
+    match ty {
+        GL_FLOAT_MAT2 | GL_FLOAT_MAT2x3 | GL_FLOAT_MAT2x4 => 2,
+        GL_FLOAT_MAT3 | GL_FLOAT_MAT3x2 | GL_FLOAT_MAT3x4 => 3,
+        GL_FLOAT_MAT4 | GL_FLOAT_MAT4x2 | GL_FLOAT_MAT4x3 => 4,
+        _ => 1,
+    }
+}
+
+/// How many components an input of type `ty` has in each location, the
+/// number of rows for matrices.
+#[allow(non_upper_case_globals)]
+fn components(ty: GLenum) -> i32 {
+    match ty {
+        GL_FLOAT_VEC2 | GL_INT_VEC2 | GL_UNSIGNED_INT_VEC2 | GL_FLOAT_MAT2 | GL_FLOAT_MAT3x2 | GL_FLOAT_MAT4x2 => 2,
+        GL_FLOAT_VEC3 | GL_INT_VEC3 | GL_UNSIGNED_INT_VEC3 | GL_FLOAT_MAT3 | GL_FLOAT_MAT2x3 | GL_FLOAT_MAT4x3 => 3,
+        GL_FLOAT_VEC4 | GL_INT_VEC4 | GL_UNSIGNED_INT_VEC4 | GL_FLOAT_MAT4 | GL_FLOAT_MAT2x4 | GL_FLOAT_MAT3x4 => 4,
+        _ => 1,
+    }
+}
+
+/// Declares a `repr(C)` vertex struct and implements [`Vertex`] for it, one
+/// attribute per field with locations counting up from 0 in field order.
+///
+/// Field types have to implement [`VertexField`]: `f32`, the integer types,
+/// arrays of them, [`Normalized`] arrays and arrays of arrays for matrices,
+/// which take one location per column. Fails to compile if the
+/// compiler slipped in padding between fields.
+#[macro_export]
+macro_rules! vertex_struct {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($field_vis:vis $field:ident : $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[repr(C)]
+        #[derive(Clone, Copy)]
+        $vis struct $name {
+            $($field_vis $field: $ty),*
+        }
+
+        const _: () = assert!(
+            ::core::mem::size_of::<$name>() == 0 $(+ ::core::mem::size_of::<$ty>())*,
+            concat!("vertex: `", stringify!($name), "` has implicit padding"),
+        );
+
+        unsafe impl $crate::bytemuck::Zeroable for $name {}
+        unsafe impl $crate::bytemuck::Pod for $name {}
+        impl $crate::vertex::Vertex for $name {
+            fn layout() -> $crate::vertex::VertexLayout {
+                $crate::vertex::VertexLayout::new(::core::mem::size_of::<$name>())
+                    $(.field::<$ty>(::core::mem::offset_of!($name, $field)))*
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inputs(list: &[(&str, i32, GLenum)]) -> HashMap<String, ActiveVariable> {
+        list.iter()
+            .map(|&(name, location, ty)| (name.to_string(), ActiveVariable { location, ty, size: 1 }))
+            .collect()
+    }
+
+    #[test]
+    fn fewer_components_are_filled_in() {
+        // a vec3 position read as a vec4, w comes out as 1
+        let layout = VertexLayout::new(12).attribute(VertexAttribute::float(0, 3, 0));
+        layout.check_inputs(&inputs(&[("pos", 0, GL_FLOAT_VEC4)])).unwrap();
+    }
+
+    #[test]
+    fn more_components_are_an_error() {
+        let layout = VertexLayout::new(16).attribute(VertexAttribute::float(0, 4, 0));
+        assert!(matches!(layout.check_inputs(&inputs(&[("pos", 0, GL_FLOAT_VEC3)])), Err(Error::VertexLayout(_))));
+    }
+
+    #[test]
+    fn missing_locations_and_integer_mismatches_are_errors() {
+        let layout = VertexLayout::new(16).attribute(VertexAttribute::float(0, 4, 0));
+        assert!(matches!(layout.check_inputs(&inputs(&[("uv", 1, GL_FLOAT_VEC2)])), Err(Error::VertexLayout(_))));
+        assert!(matches!(layout.check_inputs(&inputs(&[("id", 0, GL_INT)])), Err(Error::VertexLayout(_))));
+        // built ins have no location
+        layout.check_inputs(&inputs(&[("gl_VertexID", -1, GL_INT)])).unwrap();
+    }
+}